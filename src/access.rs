@@ -0,0 +1,173 @@
+use super::*;
+use crate::types::{AccessScope, GrantView, PatientView};
+
+/// Number of nanoseconds in an hour, the unit of `env::block_timestamp()`
+const NANOS_PER_HOUR: u64 = 3_600_000_000_000;
+
+#[near_bindgen]
+impl Contract {
+    /// Allow the given account to read the given sections of the calling patient's
+    /// information. When `expires_in_hours` is set, the access stops authorizing reads
+    /// after that many hours without the patient having to revoke it.
+    pub fn grant_access(
+        &mut self,
+        grantee: AccountId,
+        scopes: Vec<AccessScope>,
+        expires_in_hours: Option<u64>,
+    ) {
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        require!(
+            self.patients.contains_key(&account_id),
+            "Patient not found."
+        );
+        require!(grantee != account_id, "Cannot grant access to yourself.");
+        require!(!scopes.is_empty(), "At least one access scope is required.");
+        require!(
+            expires_in_hours != Some(0),
+            "Access duration must be at least one hour."
+        );
+
+        let now = env::block_timestamp();
+        let mut grants = self.active_grants(&account_id, now);
+        require!(
+            !grants.iter().any(|grant| grant.grantee() == grantee),
+            "Access already granted."
+        );
+        log!(
+            "Granting access to {} for patient with ID: {}",
+            grantee,
+            account_id
+        );
+
+        let expires_at =
+            expires_in_hours.map(|hours| now.saturating_add(hours.saturating_mul(NANOS_PER_HOUR)));
+        let mut granted_scopes: Vec<AccessScope> = Vec::with_capacity(scopes.len());
+        for scope in scopes {
+            if !granted_scopes.contains(&scope) {
+                granted_scopes.push(scope);
+            }
+        }
+        grants.push(AccessGrant::new(
+            grantee.clone(),
+            granted_scopes.clone(),
+            now,
+            expires_at,
+        ));
+        self.access_grants.insert(&account_id, &grants);
+        self.record_access(&account_id, "grant_access", granted_scopes);
+        self.charge_storage(&account_id, initial_storage);
+        Event::AccessGranted {
+            patient_id: &account_id,
+            grantee: &grantee,
+        }
+        .emit();
+        log!("Granted access for patient with ID: {}", account_id);
+    }
+
+    /// Withdraw a previously granted read access from the given account
+    pub fn revoke_access(&mut self, grantee: AccountId) {
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        require!(
+            self.patients.contains_key(&account_id),
+            "Patient not found."
+        );
+
+        let mut grants = self.active_grants(&account_id, env::block_timestamp());
+        let revoked = grants
+            .iter()
+            .position(|grant| grant.grantee() == grantee)
+            .map(|index| grants.remove(index))
+            .expect("Access grant not found.");
+        log!(
+            "Revoking access from {} for patient with ID: {}",
+            grantee,
+            account_id
+        );
+
+        self.access_grants.insert(&account_id, &grants);
+        self.record_access(&account_id, "revoke_access", revoked.scopes());
+        self.charge_storage(&account_id, initial_storage);
+        Event::AccessRevoked {
+            patient_id: &account_id,
+            grantee: &grantee,
+        }
+        .emit();
+        log!("Revoked access for patient with ID: {}", account_id);
+    }
+
+    /// List the access grants made by the calling patient, including
+    /// expired grants that have not been pruned yet.
+    /// This is a change method, as view calls can't identify the caller.
+    pub fn list_grants(&mut self) -> Vec<GrantView> {
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.patients.contains_key(&account_id),
+            "Patient not found."
+        );
+
+        let now = env::block_timestamp();
+        self.access_grants
+            .get(&account_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|grant| GrantView::new(grant, now))
+            .collect()
+    }
+
+    /// Get the sections of a patient's information that the patient
+    /// granted the calling account access to.
+    /// This is a change method so that the read is recorded in the patient's audit log,
+    /// whose storage is charged to the calling account.
+    pub fn get_patient_for(&mut self, account_id: AccountId) -> PatientView {
+        let caller = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let demographics = self.internal_demographics(&account_id);
+        let scopes = self
+            .granted_scopes(&account_id, &caller)
+            .expect("Access not granted by patient.");
+
+        self.record_access(&account_id, "get_patient_for", scopes.clone());
+        self.charge_storage(&caller, initial_storage);
+        self.internal_patient_records(&account_id)
+            .view(&demographics, &scopes)
+    }
+}
+
+impl Contract {
+    /// Check whether the patient has granted read access to the given account
+    /// and that grant has not expired yet. The contract methods read the granted
+    /// sections with `granted_scopes` instead.
+    #[cfg(test)]
+    pub fn has_access(&self, patient_id: &AccountId, grantee: &AccountId) -> bool {
+        self.granted_scopes(patient_id, grantee).is_some()
+    }
+
+    /// Return the sections the patient granted the given account access to,
+    /// or `None` if there is no unexpired grant. Guardians can read every section.
+    pub fn granted_scopes(
+        &self,
+        patient_id: &AccountId,
+        grantee: &AccountId,
+    ) -> Option<Vec<AccessScope>> {
+        if self.is_guardian(patient_id, grantee) {
+            return Some(AccessScope::all());
+        }
+
+        let now = env::block_timestamp();
+        self.access_grants
+            .get(patient_id)?
+            .into_iter()
+            .find(|grant| &grant.grantee() == grantee && !grant.is_expired(now))
+            .map(|grant| grant.scopes())
+    }
+
+    /// Return the patient's grants with the expired ones pruned.
+    /// Expired grants are only dropped when the patient next changes their grants.
+    fn active_grants(&self, patient_id: &AccountId, now: u64) -> Vec<AccessGrant> {
+        let mut grants = self.access_grants.get(patient_id).unwrap_or_default();
+        grants.retain(|grant| !grant.is_expired(now));
+        grants
+    }
+}
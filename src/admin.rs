@@ -0,0 +1,37 @@
+use super::*;
+
+#[near_bindgen]
+impl Contract {
+    /// Give the given account admin rights. Only callable by an admin.
+    pub fn add_admin(&mut self, account_id: AccountId) {
+        self.assert_admin();
+        require!(
+            self.admins.insert(&account_id),
+            "Account is already an admin."
+        );
+        log!("Added admin with ID: {}", account_id);
+    }
+
+    /// Take admin rights away from the given account. Only callable by an admin.
+    pub fn remove_admin(&mut self, account_id: AccountId) {
+        self.assert_admin();
+        require!(self.admins.len() > 1, "Cannot remove the last admin.");
+        require!(self.admins.remove(&account_id), "Admin not found.");
+        log!("Removed admin with ID: {}", account_id);
+    }
+
+    /// Get all accounts with admin rights
+    pub fn get_admins(&self) -> Vec<AccountId> {
+        self.admins.to_vec()
+    }
+}
+
+impl Contract {
+    /// Panic unless the calling account is an admin
+    pub fn assert_admin(&self) {
+        require!(
+            self.admins.contains(&env::predecessor_account_id()),
+            "Only admins can call this method."
+        );
+    }
+}
@@ -0,0 +1,364 @@
+use super::*;
+use crate::types::{AmendableRecord, RecordVersion};
+
+#[near_bindgen]
+impl Contract {
+    /// Amend a medical record of the calling account, keeping the previous versions
+    pub fn amend_medical_record(
+        &mut self,
+        record_id: u64,
+        condition: String,
+        record_data: String,
+        date: String,
+        amendment_reason: String,
+    ) {
+        let account_id = env::predecessor_account_id();
+        let record =
+            MedicalRecord::new(record_id, condition, record_data, parse_date("date", &date));
+        self.internal_amend_record(
+            account_id,
+            AmendableRecord::MedicalRecord(record),
+            amendment_reason,
+            "amend_medical_record",
+        );
+    }
+
+    /// Amend a treatment of the calling account, keeping the previous versions
+    pub fn amend_treatment(
+        &mut self,
+        record_id: u64,
+        treatment: String,
+        dosage: String,
+        start_date: String,
+        finish_date: String,
+        reason: Option<String>,
+        amendment_reason: String,
+    ) {
+        let account_id = env::predecessor_account_id();
+        let (start_date, finish_date) = parse_treatment_period(&start_date, &finish_date);
+        let treatment = Treatment::new(
+            record_id,
+            treatment,
+            dosage,
+            start_date,
+            finish_date,
+            reason,
+        );
+        self.internal_amend_record(
+            account_id,
+            AmendableRecord::Treatment(treatment),
+            amendment_reason,
+            "amend_treatment",
+        );
+    }
+
+    /// Amend a family medical record of the calling account, keeping the previous versions
+    pub fn amend_family_medical_record(
+        &mut self,
+        record_id: u64,
+        condition: String,
+        relation_to_patient: String,
+        amendment_reason: String,
+    ) {
+        let account_id = env::predecessor_account_id();
+        let record = FamilyMedicalRecord::new(record_id, condition, relation_to_patient);
+        self.internal_amend_record(
+            account_id,
+            AmendableRecord::FamilyMedicalRecord(record),
+            amendment_reason,
+            "amend_family_medical_record",
+        );
+    }
+
+    /// Amend an allergy of the calling account, keeping the previous versions
+    pub fn amend_allergy(
+        &mut self,
+        record_id: u64,
+        allergen: String,
+        severity: AllergySeverity,
+        amendment_reason: String,
+    ) {
+        let account_id = env::predecessor_account_id();
+        self.internal_amend_record(
+            account_id,
+            AmendableRecord::Allergy(Allergy::new(record_id, allergen, severity)),
+            amendment_reason,
+            "amend_allergy",
+        );
+    }
+
+    /// Amend an immunization of the calling account, keeping the previous versions
+    pub fn amend_immunization(
+        &mut self,
+        record_id: u64,
+        name: String,
+        date: String,
+        amendment_reason: String,
+    ) {
+        let account_id = env::predecessor_account_id();
+        let immunization = Immunization::new(record_id, name, parse_date("date", &date));
+        self.internal_amend_record(
+            account_id,
+            AmendableRecord::Immunization(immunization),
+            amendment_reason,
+            "amend_immunization",
+        );
+    }
+
+    /// Amend a procedure of the calling account, keeping the previous versions
+    pub fn amend_procedure(
+        &mut self,
+        record_id: u64,
+        name: String,
+        date: String,
+        description: String,
+        amendment_reason: String,
+    ) {
+        let account_id = env::predecessor_account_id();
+        let procedure = Procedure::new(record_id, name, parse_date("date", &date), description);
+        self.internal_amend_record(
+            account_id,
+            AmendableRecord::Procedure(procedure),
+            amendment_reason,
+            "amend_procedure",
+        );
+    }
+
+    /// Amend a medical record of a patient the calling account is guardian of
+    pub fn amend_medical_record_for(
+        &mut self,
+        patient_id: AccountId,
+        record_id: u64,
+        condition: String,
+        record_data: String,
+        date: String,
+        amendment_reason: String,
+    ) {
+        self.assert_guardian(&patient_id);
+        let record =
+            MedicalRecord::new(record_id, condition, record_data, parse_date("date", &date));
+        self.internal_amend_record(
+            patient_id,
+            AmendableRecord::MedicalRecord(record),
+            amendment_reason,
+            "amend_medical_record_for",
+        );
+    }
+
+    /// Amend a treatment of a patient the calling account is guardian of
+    pub fn amend_treatment_for(
+        &mut self,
+        patient_id: AccountId,
+        record_id: u64,
+        treatment: String,
+        dosage: String,
+        start_date: String,
+        finish_date: String,
+        reason: Option<String>,
+        amendment_reason: String,
+    ) {
+        self.assert_guardian(&patient_id);
+        let (start_date, finish_date) = parse_treatment_period(&start_date, &finish_date);
+        let treatment = Treatment::new(
+            record_id,
+            treatment,
+            dosage,
+            start_date,
+            finish_date,
+            reason,
+        );
+        self.internal_amend_record(
+            patient_id,
+            AmendableRecord::Treatment(treatment),
+            amendment_reason,
+            "amend_treatment_for",
+        );
+    }
+
+    /// Amend a family medical record of a patient the calling account is guardian of
+    pub fn amend_family_medical_record_for(
+        &mut self,
+        patient_id: AccountId,
+        record_id: u64,
+        condition: String,
+        relation_to_patient: String,
+        amendment_reason: String,
+    ) {
+        self.assert_guardian(&patient_id);
+        let record = FamilyMedicalRecord::new(record_id, condition, relation_to_patient);
+        self.internal_amend_record(
+            patient_id,
+            AmendableRecord::FamilyMedicalRecord(record),
+            amendment_reason,
+            "amend_family_medical_record_for",
+        );
+    }
+
+    /// Amend an allergy of a patient the calling account is guardian of
+    pub fn amend_allergy_for(
+        &mut self,
+        patient_id: AccountId,
+        record_id: u64,
+        allergen: String,
+        severity: AllergySeverity,
+        amendment_reason: String,
+    ) {
+        self.assert_guardian(&patient_id);
+        self.internal_amend_record(
+            patient_id,
+            AmendableRecord::Allergy(Allergy::new(record_id, allergen, severity)),
+            amendment_reason,
+            "amend_allergy_for",
+        );
+    }
+
+    /// Amend an immunization of a patient the calling account is guardian of
+    pub fn amend_immunization_for(
+        &mut self,
+        patient_id: AccountId,
+        record_id: u64,
+        name: String,
+        date: String,
+        amendment_reason: String,
+    ) {
+        self.assert_guardian(&patient_id);
+        let immunization = Immunization::new(record_id, name, parse_date("date", &date));
+        self.internal_amend_record(
+            patient_id,
+            AmendableRecord::Immunization(immunization),
+            amendment_reason,
+            "amend_immunization_for",
+        );
+    }
+
+    /// Amend a procedure of a patient the calling account is guardian of
+    pub fn amend_procedure_for(
+        &mut self,
+        patient_id: AccountId,
+        record_id: u64,
+        name: String,
+        date: String,
+        description: String,
+        amendment_reason: String,
+    ) {
+        self.assert_guardian(&patient_id);
+        let procedure = Procedure::new(record_id, name, parse_date("date", &date), description);
+        self.internal_amend_record(
+            patient_id,
+            AmendableRecord::Procedure(procedure),
+            amendment_reason,
+            "amend_procedure_for",
+        );
+    }
+
+    /// Get every version of one of the calling account's entries, oldest first.
+    /// This is a change method, as view calls can't identify the caller.
+    pub fn get_record_history(&mut self, record_id: u64) -> Vec<RecordVersion> {
+        let account_id = env::predecessor_account_id();
+        let entry = self
+            .internal_patient_records(&account_id)
+            .entry(record_id)
+            .expect("Record not found.");
+
+        self.internal_record_history(entry)
+    }
+
+    /// Get every version of an entry of a patient who granted the calling account
+    /// access to its section, oldest first.
+    /// This is a change method so that the read is recorded in the patient's audit log,
+    /// whose storage is charged to the calling account.
+    pub fn get_record_history_for(
+        &mut self,
+        patient_id: AccountId,
+        record_id: u64,
+    ) -> Vec<RecordVersion> {
+        let caller = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let entry = self
+            .internal_patient_records(&patient_id)
+            .entry(record_id)
+            .expect("Record not found.");
+        let scope = entry.scope();
+        require!(
+            self.granted_scopes(&patient_id, &caller)
+                .map(|scopes| scopes.contains(&scope))
+                .unwrap_or(false),
+            "Access not granted by patient."
+        );
+
+        self.record_access(&patient_id, "get_record_history_for", vec![scope]);
+        self.charge_storage(&caller, initial_storage);
+        self.internal_record_history(entry)
+    }
+}
+
+impl Contract {
+    /// Replace an entry of the given patient with an amended version,
+    /// appending it to the entry's version history
+    fn internal_amend_record(
+        &mut self,
+        account_id: AccountId,
+        amended: AmendableRecord,
+        amendment_reason: String,
+        method: &str,
+    ) {
+        let initial_storage = env::storage_usage();
+        require!(
+            !amendment_reason.trim().is_empty(),
+            "An amendment reason is required."
+        );
+        let mut records = self.internal_patient_records(&account_id);
+        let record_id = amended.id();
+        let current = records.entry(record_id).expect("Record not found.");
+        log!(
+            "Amending record {} for patient with ID: {}",
+            record_id,
+            account_id
+        );
+        log!("Reason: {}", amendment_reason);
+
+        // Replace the entry, which must be of the same kind
+        let scope = amended.scope();
+        require!(records.replace_entry(amended.clone()), "Record not found.");
+        // The amended version is unsigned, so the original signature no longer applies
+        self.internal_forget_signature(record_id);
+
+        // Append the amendment to the history, starting it with the original version
+        let mut history = self
+            .record_history
+            .get(&record_id)
+            .unwrap_or_else(|| vec![RecordVersion::original(current)]);
+        history.push(RecordVersion::amendment(
+            history.len() as u32 + 1,
+            amended,
+            env::block_timestamp(),
+            env::predecessor_account_id(),
+            amendment_reason,
+        ));
+
+        // Update the patient's collections and the history in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_history.insert(&record_id, &history);
+        self.record_access(&account_id, method, vec![scope]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAmended {
+            patient_id: &account_id,
+            record_id,
+            section: scope,
+        }
+        .emit();
+        log!(
+            "Amended record {} for patient with ID: {} (version {})",
+            record_id,
+            account_id,
+            history.len()
+        );
+    }
+
+    /// Return the versions of the entry, which only has its original version until amended
+    fn internal_record_history(&self, entry: AmendableRecord) -> Vec<RecordVersion> {
+        self.record_history
+            .get(&entry.id())
+            .unwrap_or_else(|| vec![RecordVersion::original(entry)])
+    }
+}
@@ -0,0 +1,55 @@
+use super::*;
+
+/// Maximum number of audit entries returned by one `get_access_log` call
+const MAX_ACCESS_LOG_PAGE: u64 = 100;
+
+#[near_bindgen]
+impl Contract {
+    /// Get a page of the audit log of the calling patient, oldest entries first.
+    /// At most `MAX_ACCESS_LOG_PAGE` entries are returned.
+    /// This is a change method, as view calls can't identify the caller.
+    pub fn get_access_log(&mut self, from_index: u64, limit: u64) -> Vec<AuditEntry> {
+        let account_id = env::predecessor_account_id();
+        let log = match self.audit_logs.get(&account_id) {
+            Some(log) => log,
+            None => return vec![],
+        };
+        let to_index = std::cmp::min(
+            from_index.saturating_add(std::cmp::min(limit, MAX_ACCESS_LOG_PAGE)),
+            log.len(),
+        );
+
+        (from_index..to_index)
+            .filter_map(|index| log.get(index))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Append an entry to the patient's audit log for a read or write
+    /// made by the calling account
+    pub fn record_access(
+        &mut self,
+        patient_id: &AccountId,
+        method: &str,
+        sections: Vec<AccessScope>,
+    ) {
+        let mut log = self.audit_logs.get(patient_id).unwrap_or_else(|| {
+            Vector::new(
+                StorageKey::AuditLog {
+                    account_hash: env::sha256(patient_id.as_bytes()),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+
+        log.push(&AuditEntry::new(
+            env::predecessor_account_id(),
+            env::block_timestamp(),
+            method.to_string(),
+            sections,
+        ));
+        self.audit_logs.insert(patient_id, &log);
+    }
+}
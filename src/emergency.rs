@@ -0,0 +1,110 @@
+use super::*;
+use crate::types::{EmergencyView, ProviderRole};
+
+/// Maximum number of break-glass accesses a provider can make within the window
+const EMERGENCY_ACCESS_LIMIT: usize = 5;
+/// Length of the rate limiting window in nanoseconds (24 hours)
+const EMERGENCY_ACCESS_WINDOW: u64 = 24 * 3_600_000_000_000;
+
+#[near_bindgen]
+impl Contract {
+    /// Break-glass access: let a registered emergency provider read the critical
+    /// information of a patient who cannot grant access (blood type, allergies and
+    /// active treatments). The justification is permanently logged for the patient to review,
+    /// and its storage is charged to the provider.
+    pub fn emergency_access(
+        &mut self,
+        patient_id: AccountId,
+        justification: String,
+    ) -> EmergencyView {
+        let provider_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        self.assert_provider_role(&provider_id, &[ProviderRole::Emergency]);
+        require!(
+            !self.emergency_suspended.contains(&provider_id),
+            "Emergency access has been suspended for this provider."
+        );
+        require!(
+            !justification.trim().is_empty(),
+            "A justification is required for emergency access."
+        );
+        let demographics = self.internal_demographics(&patient_id);
+
+        // Rate limit the provider over a sliding window
+        let now = env::block_timestamp();
+        let mut usage = self.emergency_usage.get(&provider_id).unwrap_or_default();
+        usage.retain(|accessed_at| now.saturating_sub(*accessed_at) < EMERGENCY_ACCESS_WINDOW);
+        require!(
+            usage.len() < EMERGENCY_ACCESS_LIMIT,
+            "Emergency access rate limit exceeded."
+        );
+        usage.push(now);
+        self.emergency_usage.insert(&provider_id, &usage);
+
+        log!(
+            "Emergency access to patient with ID: {} by provider with ID: {}",
+            patient_id,
+            provider_id
+        );
+        log!("Justification: {}", justification);
+
+        let mut accesses = self.emergency_accesses.get(&patient_id).unwrap_or_default();
+        accesses.push(EmergencyAccess::new(
+            provider_id.clone(),
+            justification,
+            now,
+        ));
+        self.emergency_accesses.insert(&patient_id, &accesses);
+        self.record_access(
+            &patient_id,
+            "emergency_access",
+            vec![
+                AccessScope::Demographics,
+                AccessScope::Allergies,
+                AccessScope::Treatments,
+            ],
+        );
+        self.charge_storage(&provider_id, initial_storage);
+
+        self.internal_patient_records(&patient_id)
+            .emergency_view(&demographics, &today())
+    }
+
+    /// Get the break-glass accesses made to the calling patient's information.
+    /// This is a change method, as view calls can't identify the caller.
+    pub fn get_emergency_accesses(&mut self) -> Vec<EmergencyAccess> {
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.patients.contains_key(&account_id),
+            "Patient not found."
+        );
+
+        self.emergency_accesses.get(&account_id).unwrap_or_default()
+    }
+
+    /// Prevent a provider from using break-glass access. Only callable by an admin.
+    pub fn suspend_emergency_access(&mut self, provider_id: AccountId) {
+        self.assert_admin();
+        require!(
+            self.emergency_suspended.insert(&provider_id),
+            "Emergency access is already suspended for this provider."
+        );
+        log!(
+            "Suspended emergency access for provider with ID: {}",
+            provider_id
+        );
+    }
+
+    /// Allow a suspended provider to use break-glass access again. Only callable by an admin.
+    pub fn restore_emergency_access(&mut self, provider_id: AccountId) {
+        self.assert_admin();
+        require!(
+            self.emergency_suspended.remove(&provider_id),
+            "Emergency access is not suspended for this provider."
+        );
+        log!(
+            "Restored emergency access for provider with ID: {}",
+            provider_id
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use super::*;
+use near_sdk::{json_types::U128, serde::Serialize, serde_json};
+
+/// Standard and version of the events emitted by the contract, as defined by NEP-297
+const EVENT_STANDARD: &str = "medibridge";
+const EVENT_VERSION: &str = "1.0.0";
+
+// Enum representing the state changes reported to indexers.
+// The data only identifies accounts, entries and sections, never their content.
+#[derive(Serialize)]
+#[serde(
+    crate = "near_sdk::serde",
+    tag = "event",
+    content = "data",
+    rename_all = "snake_case"
+)]
+pub enum Event<'a> {
+    PatientRegistered {
+        patient_id: &'a AccountId,
+    },
+    PatientRemoved {
+        patient_id: &'a AccountId,
+    },
+    DemographicsUpdated {
+        patient_id: &'a AccountId,
+        field: &'a str,
+    },
+    RecordAdded {
+        patient_id: &'a AccountId,
+        record_id: u64,
+        section: AccessScope,
+    },
+    RecordAmended {
+        patient_id: &'a AccountId,
+        record_id: u64,
+        section: AccessScope,
+    },
+    RecordRetracted {
+        patient_id: &'a AccountId,
+        record_id: u64,
+        section: AccessScope,
+    },
+    /// Not linked to the patient, as public records are anonymous
+    PublicRecordPublished {
+        index: u64,
+        section: AccessScope,
+    },
+    PublicRecordWithdrawn {
+        index: u64,
+        section: AccessScope,
+    },
+    AccessGranted {
+        patient_id: &'a AccountId,
+        grantee: &'a AccountId,
+    },
+    AccessRevoked {
+        patient_id: &'a AccountId,
+        grantee: &'a AccountId,
+    },
+    Payout {
+        recipients: &'a [AccountId],
+        amount: U128,
+    },
+}
+
+// Struct representing an event in the NEP-297 format
+#[derive(Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a Event<'a>,
+}
+
+impl Event<'_> {
+    /// Log the event as an `EVENT_JSON:` line
+    pub fn emit(&self) {
+        let log = EventLog {
+            standard: EVENT_STANDARD,
+            version: EVENT_VERSION,
+            event: self,
+        };
+        env::log_str(&format!(
+            "EVENT_JSON:{}",
+            serde_json::to_string(&log).unwrap()
+        ));
+    }
+}
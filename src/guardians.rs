@@ -0,0 +1,455 @@
+use super::*;
+
+/// Age (in years) at which a dependant can take over control of their information
+const AGE_OF_MAJORITY: u16 = 18;
+/// Maximum length (in bytes) of the name given in a dependant registration
+const MAX_DEPENDANT_NAME_LENGTH: usize = 100;
+
+#[near_bindgen]
+impl Contract {
+    /// Ask to register a minor as a patient on behalf of their account, making the
+    /// calling account their guardian until they reach the age of majority once the
+    /// dependant's account accepts with `accept_guardianship`. The attached deposit,
+    /// at least the minimum storage balance, is held until then. The storage of the
+    /// request itself is charged to the guardian until it is accepted, declined or cancelled.
+    #[payable]
+    pub fn register_dependant(
+        &mut self,
+        dependant_id: AccountId,
+        full_name: String,
+        birthday: String,
+        gender: Gender,
+        blood_type: BloodType,
+    ) {
+        let guardian_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        require!(
+            dependant_id != guardian_id,
+            "Cannot register yourself as a dependant."
+        );
+        require!(
+            full_name.len() <= MAX_DEPENDANT_NAME_LENGTH,
+            "Full name is too long."
+        );
+        require!(
+            !self.patients.contains_key(&dependant_id),
+            "Patient already exists."
+        );
+        let birthday = parse_date("birthday", &birthday);
+        require!(
+            today() < birthday.add_years(AGE_OF_MAJORITY),
+            "Dependant has already reached majority."
+        );
+        let deposit = env::attached_deposit();
+        require!(
+            deposit >= self.storage_balance_bounds().min().0,
+            "The attached deposit is less than the minimum storage balance."
+        );
+
+        let mut pending = self
+            .pending_guardianships
+            .get(&dependant_id)
+            .unwrap_or_default();
+        require!(
+            pending
+                .iter()
+                .all(|guardianship| guardianship.guardian_id() != guardian_id),
+            "Dependant registration already requested."
+        );
+        pending.push(PendingGuardianship::new(
+            guardian_id.clone(),
+            full_name,
+            birthday,
+            gender,
+            blood_type,
+            deposit,
+        ));
+        self.pending_guardianships.insert(&dependant_id, &pending);
+        self.charge_storage(&guardian_id, initial_storage);
+        log!(
+            "Requested registration of dependant with ID: {} for guardian with ID: {}",
+            dependant_id,
+            guardian_id
+        );
+    }
+
+    /// Accept the given account's request to register the calling account as their
+    /// dependant. The calling account is registered as a patient under their
+    /// guardianship, and the other requests are declined.
+    pub fn accept_guardianship(&mut self, guardian_id: AccountId) {
+        let dependant_id = env::predecessor_account_id();
+        let pending = self
+            .pending_guardianships
+            .get(&dependant_id)
+            .expect("Dependant registration not found.");
+        require!(
+            pending
+                .iter()
+                .any(|guardianship| guardianship.guardian_id() == guardian_id),
+            "Dependant registration not found."
+        );
+        let mut accepted = None;
+        for guardianship in pending {
+            let removed =
+                self.internal_take_pending_guardianship(&dependant_id, &guardianship.guardian_id());
+            if removed.guardian_id() == guardian_id {
+                accepted = Some(removed);
+            } else {
+                Promise::new(removed.guardian_id()).transfer(removed.deposit());
+            }
+        }
+        let accepted = accepted.expect("Dependant registration not found.");
+        let majority_at = accepted.birthday().add_years(AGE_OF_MAJORITY);
+        require!(
+            today() < majority_at,
+            "Dependant has already reached majority."
+        );
+
+        self.internal_storage_deposit(&dependant_id, accepted.deposit());
+        self.internal_add_patient(
+            dependant_id.clone(),
+            accepted.full_name(),
+            accepted.birthday().to_string(),
+            accepted.gender(),
+            accepted.blood_type(),
+            "accept_guardianship",
+        );
+        let initial_storage = env::storage_usage();
+        self.guardianships.insert(
+            &dependant_id,
+            &Guardianship::new(vec![guardian_id.clone()], Some(majority_at)),
+        );
+        self.charge_storage(&dependant_id, initial_storage);
+        log!(
+            "Registered dependant with ID: {} for guardian with ID: {}",
+            dependant_id,
+            guardian_id
+        );
+    }
+
+    /// Decline the given account's request to register the calling account
+    /// as their dependant, refunding their deposit
+    pub fn decline_guardianship(&mut self, guardian_id: AccountId) {
+        let dependant_id = env::predecessor_account_id();
+        let removed = self.internal_take_pending_guardianship(&dependant_id, &guardian_id);
+        Promise::new(removed.guardian_id()).transfer(removed.deposit());
+        log!(
+            "Declined guardianship of account with ID: {} by guardian with ID: {}",
+            dependant_id,
+            guardian_id
+        );
+    }
+
+    /// Withdraw the calling account's request to register the given
+    /// account as their dependant, refunding the deposit
+    pub fn cancel_dependant_registration(&mut self, dependant_id: AccountId) {
+        let guardian_id = env::predecessor_account_id();
+        let removed = self.internal_take_pending_guardianship(&dependant_id, &guardian_id);
+        Promise::new(removed.guardian_id()).transfer(removed.deposit());
+        log!(
+            "Cancelled registration of dependant with ID: {} by guardian with ID: {}",
+            dependant_id,
+            guardian_id
+        );
+    }
+
+    /// Get the requests to register the calling account as a dependant.
+    /// This is a change method, as view calls can't identify the caller.
+    pub fn get_pending_guardianships(&mut self) -> Vec<PendingGuardianship> {
+        let account_id = env::predecessor_account_id();
+        self.pending_guardianships
+            .get(&account_id)
+            .unwrap_or_default()
+    }
+
+    /// Allow the given account to manage the calling patient's information
+    pub fn add_guardian(&mut self, guardian_id: AccountId) {
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        require!(
+            self.patients.contains_key(&account_id),
+            "Patient not found."
+        );
+        require!(guardian_id != account_id, "Cannot be your own guardian.");
+
+        let mut guardianship = self
+            .guardianships
+            .get(&account_id)
+            .unwrap_or_else(|| Guardianship::new(vec![], None));
+        require!(
+            !guardianship.is_minor(&today()),
+            "Dependants cannot manage their guardians before reaching majority."
+        );
+        require!(
+            !guardianship.is_guardian(&guardian_id),
+            "Account is already a guardian."
+        );
+
+        guardianship.add_guardian(guardian_id.clone());
+        self.guardianships.insert(&account_id, &guardianship);
+        // Guardians manage every section of the patient's information
+        self.record_access(&account_id, "add_guardian", AccessScope::all());
+        self.charge_storage(&account_id, initial_storage);
+        log!(
+            "Added guardian with ID: {} for patient with ID: {}",
+            guardian_id,
+            account_id
+        );
+    }
+
+    /// Stop the given account from managing the calling patient's information
+    pub fn remove_guardian(&mut self, guardian_id: AccountId) {
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let mut guardianship = self
+            .guardianships
+            .get(&account_id)
+            .expect("Guardian not found.");
+        require!(
+            !guardianship.is_minor(&today()),
+            "Dependants cannot manage their guardians before reaching majority."
+        );
+        require!(
+            guardianship.is_guardian(&guardian_id),
+            "Guardian not found."
+        );
+
+        guardianship.remove_guardian(&guardian_id);
+        self.guardianships.insert(&account_id, &guardianship);
+        self.record_access(&account_id, "remove_guardian", AccessScope::all());
+        self.charge_storage(&account_id, initial_storage);
+        log!(
+            "Removed guardian with ID: {} for patient with ID: {}",
+            guardian_id,
+            account_id
+        );
+    }
+
+    /// Take over control of the calling patient's information after reaching
+    /// majority, removing every guardian appointed while they were a minor
+    pub fn take_control(&mut self) {
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let guardianship = self
+            .guardianships
+            .get(&account_id)
+            .expect("Guardian not found.");
+        let majority_at = guardianship
+            .majority_at()
+            .expect("Patient is not a dependant minor.");
+        require!(
+            today() >= majority_at,
+            "Patient has not reached majority yet."
+        );
+
+        self.guardianships.remove(&account_id);
+        self.record_access(&account_id, "take_control", AccessScope::all());
+        self.charge_storage(&account_id, initial_storage);
+        log!("Patient with ID: {} has taken over control.", account_id);
+    }
+
+    /// Get the guardians of the calling patient.
+    /// This is a change method, as view calls can't identify the caller.
+    pub fn get_guardians(&mut self) -> Vec<AccountId> {
+        let account_id = env::predecessor_account_id();
+        self.guardianships
+            .get(&account_id)
+            .map(|guardianship| guardianship.guardians())
+            .unwrap_or_default()
+    }
+
+    /// Update the name of a patient the calling account is guardian of
+    pub fn full_name_for(&mut self, patient_id: AccountId, full_name: String) {
+        self.assert_guardian(&patient_id);
+        self.internal_full_name(patient_id, full_name, "full_name_for");
+    }
+
+    /// Update the birthday of a patient the calling account is guardian of
+    pub fn birthday_for(&mut self, patient_id: AccountId, birthday: String) {
+        self.assert_guardian(&patient_id);
+        self.internal_birthday(patient_id, birthday, "birthday_for");
+    }
+
+    /// Update the gender of a patient the calling account is guardian of
+    pub fn gender_for(&mut self, patient_id: AccountId, gender: Gender) {
+        self.assert_guardian(&patient_id);
+        self.internal_gender(patient_id, gender, "gender_for");
+    }
+
+    /// Update the blood type of a patient the calling account is guardian of
+    pub fn blood_type_for(&mut self, patient_id: AccountId, blood_type: BloodType) {
+        self.assert_guardian(&patient_id);
+        self.internal_blood_type(patient_id, blood_type, "blood_type_for");
+    }
+
+    /// Add a new allergy for a patient the calling account is guardian of.
+    /// Returns the ID of the new entry.
+    pub fn add_allergy_for(
+        &mut self,
+        patient_id: AccountId,
+        allergen: String,
+        severity: AllergySeverity,
+    ) -> u64 {
+        self.assert_guardian(&patient_id);
+        self.internal_add_allergy(patient_id, allergen, severity, "add_allergy_for")
+    }
+
+    /// Add a new immunization for a patient the calling account is guardian of.
+    /// Returns the ID of the new entry.
+    pub fn add_immunization_for(
+        &mut self,
+        patient_id: AccountId,
+        name: String,
+        date: String,
+    ) -> u64 {
+        self.assert_guardian(&patient_id);
+        self.internal_add_immunization(patient_id, name, date, "add_immunization_for")
+    }
+
+    /// Add a new procedure for a patient the calling account is guardian of.
+    /// Returns the ID of the new entry.
+    pub fn add_procedure_for(
+        &mut self,
+        patient_id: AccountId,
+        name: String,
+        date: String,
+        description: String,
+    ) -> u64 {
+        self.assert_guardian(&patient_id);
+        self.internal_add_procedure(patient_id, name, date, description, "add_procedure_for")
+    }
+
+    /// Add a new medical record for a patient the calling account is guardian of.
+    /// Returns the ID of the new entry.
+    pub fn add_medical_record_for(
+        &mut self,
+        patient_id: AccountId,
+        condition: String,
+        record_data: String,
+        date: String,
+    ) -> u64 {
+        self.assert_guardian(&patient_id);
+        self.internal_add_medical_record(
+            patient_id,
+            condition,
+            record_data,
+            date,
+            "add_medical_record_for",
+        )
+    }
+
+    /// Add a new PUBLIC medical record to the medical records of a patient the calling account
+    /// is guardian of and to the public records, carrying the given contributor commitment.
+    /// Returns the ID of the new entry.
+    pub fn add_public_medical_record_for(
+        &mut self,
+        patient_id: AccountId,
+        condition: String,
+        record_data: String,
+        date: String,
+        contributor_commitment: Base64VecU8,
+    ) -> u64 {
+        self.assert_guardian(&patient_id);
+        self.internal_add_public_medical_record(
+            patient_id,
+            condition,
+            record_data,
+            date,
+            contributor_commitment,
+            "add_public_medical_record_for",
+        )
+    }
+
+    /// Add a new treatment for a patient the calling account is guardian of.
+    /// Returns the ID of the new entry.
+    pub fn add_treatment_for(
+        &mut self,
+        patient_id: AccountId,
+        treatment: String,
+        dosage: String,
+        start_date: String,
+        finish_date: String,
+        reason: Option<String>,
+        public: bool,
+        contributor_commitment: Option<Base64VecU8>,
+    ) -> u64 {
+        self.assert_guardian(&patient_id);
+        self.internal_add_treatment(
+            patient_id,
+            treatment,
+            dosage,
+            start_date,
+            finish_date,
+            reason,
+            public,
+            contributor_commitment,
+            "add_treatment_for",
+        )
+    }
+
+    /// Add a new family medical record for a patient the calling account is guardian of.
+    /// Returns the ID of the new entry.
+    pub fn add_family_medical_record_for(
+        &mut self,
+        patient_id: AccountId,
+        condition: String,
+        relation_to_patient: String,
+        public: bool,
+        contributor_commitment: Option<Base64VecU8>,
+    ) -> u64 {
+        self.assert_guardian(&patient_id);
+        self.internal_add_family_medical_record(
+            patient_id,
+            condition,
+            relation_to_patient,
+            public,
+            contributor_commitment,
+            "add_family_medical_record_for",
+        )
+    }
+}
+
+impl Contract {
+    /// Remove and return the given guardian's request to register the dependant,
+    /// releasing the storage it used to the guardian. The deposit held with it
+    /// is left to the caller.
+    fn internal_take_pending_guardianship(
+        &mut self,
+        dependant_id: &AccountId,
+        guardian_id: &AccountId,
+    ) -> PendingGuardianship {
+        let initial_storage = env::storage_usage();
+        let mut pending = self
+            .pending_guardianships
+            .get(dependant_id)
+            .expect("Dependant registration not found.");
+        let position = pending
+            .iter()
+            .position(|guardianship| &guardianship.guardian_id() == guardian_id)
+            .expect("Dependant registration not found.");
+        let removed = pending.remove(position);
+        if pending.is_empty() {
+            self.pending_guardianships.remove(dependant_id);
+        } else {
+            self.pending_guardianships.insert(dependant_id, &pending);
+        }
+        self.charge_storage(guardian_id, initial_storage);
+        removed
+    }
+
+    /// Check whether the given account is a guardian of the patient
+    pub fn is_guardian(&self, patient_id: &AccountId, account_id: &AccountId) -> bool {
+        self.guardianships
+            .get(patient_id)
+            .map(|guardianship| guardianship.is_guardian(account_id))
+            .unwrap_or(false)
+    }
+
+    /// Panic unless the calling account is a guardian of the patient
+    pub fn assert_guardian(&self, patient_id: &AccountId) {
+        require!(
+            self.is_guardian(patient_id, &env::predecessor_account_id()),
+            "Only guardians of the patient can call this method."
+        );
+    }
+}
@@ -0,0 +1,452 @@
+use super::*;
+use crate::types::{PublicRecordV1, VersionedPatient, VersionedPublicRecord};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+// Borsh layouts of the data stored before dates, blood types,
+// genders and allergy severities were validated.
+// They are only used to migrate existing patients and public records
+// to the current layout.
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyTreatment {
+    pub id: u64,
+    pub treatment: String,
+    pub dosage: String,
+    pub start_date: String,
+    pub finish_date: String,
+    pub reason: Option<String>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyFamilyMedicalRecord {
+    pub id: u64,
+    pub condition: String,
+    pub relation_to_patient: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyMedicalRecord {
+    pub id: u64,
+    pub condition: String,
+    pub record_data: String,
+    pub date: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum LegacyRecordType {
+    MedicalRecord(LegacyMedicalRecord),
+    Treatment(LegacyTreatment),
+    FamilyMedicalRecord(LegacyFamilyMedicalRecord),
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyAllergy {
+    pub allergen: String,
+    pub severity: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyImmunization {
+    pub name: String,
+    pub date: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyProcedure {
+    pub name: String,
+    pub date: String,
+    pub description: String,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyPatient {
+    pub full_name: String,
+    pub birthday: String,
+    pub gender: String,
+    pub blood_type: String,
+    pub records: Vec<LegacyRecordType>,
+    pub allergies: Vec<LegacyAllergy>,
+    pub immunizations: Vec<LegacyImmunization>,
+    pub procedures: Vec<LegacyProcedure>,
+}
+
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct LegacyPublicRecord {
+    pub record_type: LegacyRecordType,
+    pub birthday: String,
+    pub gender: String,
+}
+
+// Borsh layout of the contract state written by the first version of the contract.
+// The collections keep their prefixes; the values they hold are converted
+// afterwards by `migrate_legacy_patients` and `migrate_legacy_public_records`.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV1 {
+    pub patients: LookupMap<AccountId, VersionedPatient>,
+    pub public_records: Vector<VersionedPublicRecord>,
+}
+
+// Borsh layout of the contract state written by the version that introduced
+// `StateVersion::V2`, before the contract had an owner, signature nonces,
+// dependant registrations or public record indices.
+// The collections keep their prefixes and the values they hold are read as they are.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct ContractV2 {
+    pub patients: LookupMap<AccountId, VersionedPatient>,
+    pub patient_records: LookupMap<AccountId, PatientRecords>,
+    pub providers: LookupMap<AccountId, Provider>,
+    pub access_grants: LookupMap<AccountId, Vec<AccessGrant>>,
+    pub admins: UnorderedSet<AccountId>,
+    pub emergency_accesses: LookupMap<AccountId, Vec<EmergencyAccess>>,
+    pub emergency_usage: LookupMap<AccountId, Vec<u64>>,
+    pub emergency_suspended: LookupSet<AccountId>,
+    pub audit_logs: LookupMap<AccountId, Vector<AuditEntry>>,
+    pub guardianships: LookupMap<AccountId, Guardianship>,
+    pub pending_records: LookupMap<AccountId, Vec<PendingRecord>>,
+    pub next_pending_id: u64,
+    pub next_record_id: u64,
+    pub record_history: LookupMap<u64, Vec<RecordVersion>>,
+    pub retracted_records: LookupMap<AccountId, Vec<Tombstone>>,
+    pub public_records: Vector<VersionedPublicRecord>,
+    pub storage_accounts: LookupMap<AccountId, StorageAccount>,
+}
+
+/// Convert a free-form legacy date, naming the field in the error
+fn convert_date(field: &str, value: &str) -> Result<Date, String> {
+    Date::from_legacy(value).ok_or_else(|| format!("Cannot convert {} '{}'.", field, value))
+}
+
+/// Convert a free-form string to its typed value, accepting common spellings
+fn convert<T: FromStr>(field: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Cannot convert {} '{}'.", field, value))
+}
+
+/// Take the next record ID from the contract's counter
+fn assign_id(next_record_id: &mut u64) -> u64 {
+    let id = *next_record_id;
+    *next_record_id += 1;
+    id
+}
+
+impl LegacyRecordType {
+    /// Convert the record to the current layout.
+    /// Caller-supplied legacy IDs were not unique, so a new ID is assigned.
+    pub fn into_current(self, next_record_id: &mut u64) -> Result<RecordType, String> {
+        Ok(match self {
+            LegacyRecordType::MedicalRecord(record) => {
+                RecordType::MedicalRecord(MedicalRecord::new(
+                    assign_id(next_record_id),
+                    record.condition,
+                    record.record_data,
+                    convert_date("date", &record.date)?,
+                ))
+            }
+            LegacyRecordType::Treatment(treatment) => RecordType::Treatment(Treatment::new(
+                assign_id(next_record_id),
+                treatment.treatment,
+                treatment.dosage,
+                convert_date("start_date", &treatment.start_date)?,
+                convert_date("finish_date", &treatment.finish_date)?,
+                treatment.reason,
+            )),
+            LegacyRecordType::FamilyMedicalRecord(record) => {
+                RecordType::FamilyMedicalRecord(FamilyMedicalRecord::new(
+                    assign_id(next_record_id),
+                    record.condition,
+                    record.relation_to_patient,
+                ))
+            }
+        })
+    }
+}
+
+/// Replace a free-form value with its correction, if one was supplied
+fn correct(value: &mut String, corrections: &HashMap<String, String>) {
+    if let Some(corrected) = corrections.get(value) {
+        *value = corrected.clone();
+    }
+}
+
+impl LegacyPatient {
+    /// Replace the free-form values that have a correction, keyed by the stored value
+    pub fn correct(&mut self, corrections: &HashMap<String, String>) {
+        correct(&mut self.birthday, corrections);
+        correct(&mut self.gender, corrections);
+        correct(&mut self.blood_type, corrections);
+        for record in self.records.iter_mut() {
+            match record {
+                LegacyRecordType::MedicalRecord(record) => correct(&mut record.date, corrections),
+                LegacyRecordType::Treatment(treatment) => {
+                    correct(&mut treatment.start_date, corrections);
+                    correct(&mut treatment.finish_date, corrections);
+                }
+                LegacyRecordType::FamilyMedicalRecord(_) => {}
+            }
+        }
+        for allergy in self.allergies.iter_mut() {
+            correct(&mut allergy.severity, corrections);
+        }
+        for immunization in self.immunizations.iter_mut() {
+            correct(&mut immunization.date, corrections);
+        }
+        for procedure in self.procedures.iter_mut() {
+            correct(&mut procedure.date, corrections);
+        }
+    }
+
+    /// Convert the patient to the current layout, assigning new IDs to their records
+    pub fn into_current(self, next_record_id: &mut u64) -> Result<Patient, String> {
+        let records = self
+            .records
+            .into_iter()
+            .map(|record| record.into_current(next_record_id))
+            .collect::<Result<Vec<_>, _>>()?;
+        let allergies = self
+            .allergies
+            .into_iter()
+            .map(|allergy| {
+                Ok(Allergy::new(
+                    assign_id(next_record_id),
+                    allergy.allergen,
+                    convert("severity", &allergy.severity)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let immunizations = self
+            .immunizations
+            .into_iter()
+            .map(|immunization| {
+                Ok(Immunization::new(
+                    assign_id(next_record_id),
+                    immunization.name,
+                    convert_date("date", &immunization.date)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let procedures = self
+            .procedures
+            .into_iter()
+            .map(|procedure| {
+                Ok(Procedure::new(
+                    assign_id(next_record_id),
+                    procedure.name,
+                    convert_date("date", &procedure.date)?,
+                    procedure.description,
+                ))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+
+        Ok(Patient::new(
+            self.full_name,
+            convert_date("birthday", &self.birthday)?,
+            convert("gender", &self.gender)?,
+            convert("blood_type", &self.blood_type)?,
+            records,
+            allergies,
+            immunizations,
+            procedures,
+        ))
+    }
+}
+
+impl LegacyPublicRecord {
+    /// Convert the public record to the current layout, assigning a new ID to its record
+    /// and generalizing the patient's age
+    pub fn into_current(
+        self,
+        next_record_id: &mut u64,
+        generalization: AgeGeneralization,
+    ) -> Result<PublicRecord, String> {
+        let record = PublicRecordV1::new(
+            self.record_type.into_current(next_record_id)?,
+            convert_date("birthday", &self.birthday)?,
+            convert("gender", &self.gender)?,
+        );
+        Ok(record.generalize(generalization, today()))
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Convert the state written by a previous version of the contract to the current layout.
+    /// Patients and public records stored by the first version are then converted
+    /// with `migrate_legacy_patients` and `migrate_legacy_public_records`.
+    /// The contract account becomes the owner and the first admin of a migrated first version,
+    /// and the owner of a migrated second version, whose admins are kept.
+    #[private]
+    #[init(ignore_state)]
+    pub fn migrate() -> Self {
+        let version = env::storage_read(STATE_VERSION_KEY)
+            .map(|bytes| StateVersion::try_from_slice(&bytes).expect("Invalid state version."))
+            .unwrap_or(StateVersion::V1);
+
+        let contract = match version {
+            StateVersion::V1 => {
+                let state: ContractV1 = env::state_read().expect("Contract state not found.");
+                let mut contract = Contract::initial_state(env::current_account_id());
+                contract.patients = state.patients;
+                contract.public_records = state.public_records;
+                log!("Migrated contract state from version 1");
+                contract
+            }
+            StateVersion::V2 => {
+                let state: ContractV2 = env::state_read().expect("Contract state not found.");
+                // The admins are kept as they are, so the owner is not added to them
+                let mut contract =
+                    Contract::state_with_admins(env::current_account_id(), state.admins);
+                contract.patients = state.patients;
+                contract.patient_records = state.patient_records;
+                contract.providers = state.providers;
+                contract.access_grants = state.access_grants;
+                contract.emergency_accesses = state.emergency_accesses;
+                contract.emergency_usage = state.emergency_usage;
+                contract.emergency_suspended = state.emergency_suspended;
+                contract.audit_logs = state.audit_logs;
+                contract.guardianships = state.guardianships;
+                contract.pending_records = state.pending_records;
+                contract.next_pending_id = state.next_pending_id;
+                contract.next_record_id = state.next_record_id;
+                contract.record_history = state.record_history;
+                contract.retracted_records = state.retracted_records;
+                contract.public_records = state.public_records;
+                contract.storage_accounts = state.storage_accounts;
+                log!("Migrated contract state from version 2");
+                contract
+            }
+            StateVersion::V3 => env::state_read().expect("Contract state not found."),
+        };
+        env::storage_write(STATE_VERSION_KEY, &StateVersion::V3.try_to_vec().unwrap());
+        contract
+    }
+
+    /// Convert patients stored with free-form strings to validated dates and typed demographics.
+    /// Patients already in the current layout are left untouched.
+    /// Returns the accounts whose data could not be converted. Only callable by an admin.
+    pub fn migrate_legacy_patients(&mut self, account_ids: Vec<AccountId>) -> Vec<AccountId> {
+        self.assert_admin();
+        let mut failed = vec![];
+
+        for account_id in account_ids {
+            let bytes = match env::storage_read(&legacy_patient_key(&account_id)) {
+                Some(bytes) => bytes,
+                None => continue,
+            };
+            if VersionedPatient::try_from_slice(&bytes).is_ok() {
+                continue;
+            }
+
+            if let Err(err) =
+                self.internal_migrate_legacy_patient(&account_id, &bytes, &HashMap::new())
+            {
+                log!("Cannot migrate patient with ID: {}. {}", account_id, err);
+                failed.push(account_id);
+            }
+        }
+
+        failed
+    }
+
+    /// Convert a patient whose free-form strings could not be converted by `migrate_legacy_patients`,
+    /// replacing the stored values that have a correction first, e.g. `{"30": "1995-07-30"}`.
+    /// Only callable by the patient or an admin.
+    pub fn correct_legacy_patient(
+        &mut self,
+        account_id: AccountId,
+        corrections: HashMap<String, String>,
+    ) {
+        let caller = env::predecessor_account_id();
+        require!(
+            caller == account_id || self.admins.contains(&caller),
+            "Only the patient or an admin can call this method."
+        );
+        let bytes =
+            env::storage_read(&legacy_patient_key(&account_id)).expect("Patient not found.");
+        require!(
+            VersionedPatient::try_from_slice(&bytes).is_err(),
+            "Patient already migrated."
+        );
+
+        if let Err(err) = self.internal_migrate_legacy_patient(&account_id, &bytes, &corrections) {
+            env::panic_str(&format!(
+                "Cannot migrate patient with ID: {}. {}",
+                account_id, err
+            ));
+        }
+    }
+
+    /// Convert a page of public records stored with free-form strings to validated dates and genders,
+    /// adding them to the secondary indices.
+    /// Returns the indices of the records that could not be converted. Only callable by an admin.
+    pub fn migrate_legacy_public_records(&mut self, from_index: u64, limit: u64) -> Vec<u64> {
+        self.assert_admin();
+        let mut failed = vec![];
+        let to_index = std::cmp::min(from_index.saturating_add(limit), self.public_records.len());
+
+        for index in from_index..to_index {
+            let key = [b"public_records".as_slice(), &index.to_le_bytes()].concat();
+            let bytes = env::storage_read(&key).expect("Public record not found.");
+            if VersionedPublicRecord::try_from_slice(&bytes).is_ok() {
+                continue;
+            }
+
+            match LegacyPublicRecord::try_from_slice(&bytes)
+                .map_err(|err| err.to_string())
+                .and_then(|record| {
+                    record.into_current(&mut self.next_record_id, self.age_generalization)
+                }) {
+                Ok(record) => {
+                    // Overwrite the raw value, as `Vector::replace` would try
+                    // to deserialize the legacy value it replaces
+                    let versioned = VersionedPublicRecord::from(record.clone());
+                    env::storage_write(&key, &versioned.try_to_vec().unwrap());
+                    self.index_public_record(index, &record);
+                }
+                Err(err) => {
+                    log!("Cannot migrate public record {}. {}", index, err);
+                    failed.push(index);
+                }
+            }
+        }
+
+        failed
+    }
+}
+
+/// Get the storage key of the patient's raw value in the `patients` map
+fn legacy_patient_key(account_id: &AccountId) -> Vec<u8> {
+    [b"patients".as_slice(), &account_id.try_to_vec().unwrap()].concat()
+}
+
+impl Contract {
+    /// Convert the patient's raw legacy value to the current layout, applying the corrections
+    fn internal_migrate_legacy_patient(
+        &mut self,
+        account_id: &AccountId,
+        bytes: &[u8],
+        corrections: &HashMap<String, String>,
+    ) -> Result<(), String> {
+        let mut patient = LegacyPatient::try_from_slice(bytes).map_err(|err| err.to_string())?;
+        patient.correct(corrections);
+        let patient = patient.into_current(&mut self.next_record_id)?;
+
+        // Overwrite the raw value with the demographics, as `LookupMap::insert`
+        // would try to deserialize the legacy value it replaces
+        let demographics = VersionedPatient::from(patient.demographics());
+        env::storage_write(
+            &legacy_patient_key(account_id),
+            &demographics.try_to_vec().unwrap(),
+        );
+
+        // Move the entries to the patient's collections
+        let mut records = PatientRecords::new(account_id);
+        for entry in patient.entries() {
+            records.add_entry(entry);
+        }
+        self.patient_records.insert(account_id, &records);
+        log!("Migrated patient with ID: {}", account_id);
+        Ok(())
+    }
+}
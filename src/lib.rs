@@ -1,89 +1,160 @@
+// `near_bindgen` generates `ContractExt` methods that take the same arguments
+// as the contract methods, so `too_many_arguments` can't be allowed per method.
+#![allow(clippy::too_many_arguments)]
+
+use crate::events::Event;
+use crate::research::{PublicRecordIndex, QuasiIdentifier};
+use crate::statistics::PublicRecordCounter;
 use crate::types::{
-    Allergy, FamilyMedicalRecord, Immunization, MedicalRecord, Patient, Procedure, PublicRecord,
-    RecordType, Treatment,
+    AccessGrant, AccessScope, AgeGeneralization, Allergy, AllergySeverity, AuditEntry, BloodType,
+    Date, Demographics, EmergencyAccess, FamilyMedicalRecord, Gender, GeneralizedAge, Guardianship,
+    Immunization, MedicalRecord, Patient, PatientRecords, PendingGuardianship, PendingRecord,
+    Procedure, Provider, PublicRecord, RecordType, RecordVersion, StorageAccount, Tombstone,
+    Treatment, VersionedPatient, VersionedPublicRecord,
 };
+use crate::withdrawals::PublicRecordLocation;
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
-    collections::{LookupMap, Vector},
-    env, log, near_bindgen, require, AccountId, PanicOnDefault, Promise,
+    collections::{LookupMap, LookupSet, TreeMap, UnorderedMap, UnorderedSet, Vector},
+    env,
+    json_types::{Base64VecU8, U128},
+    log, near_bindgen, require, AccountId, Balance, PanicOnDefault, Promise, PublicKey,
 };
 
+mod access;
+mod admin;
+mod amendments;
+mod audit;
+mod emergency;
+mod events;
+mod guardians;
+mod legacy;
+mod owner;
+mod pending;
+mod providers;
+mod research;
+mod retractions;
+mod signatures;
+mod statistics;
+mod storage;
 #[cfg(test)]
 mod tests;
 mod types;
+mod withdrawals;
+
+/// Storage keys of the collections nested inside the contract's top-level collections
+#[derive(BorshSerialize)]
+pub(crate) enum StorageKey {
+    AuditLog { account_hash: Vec<u8> },
+    PatientRecords { account_hash: Vec<u8> },
+    PatientAllergies { account_hash: Vec<u8> },
+    PatientImmunizations { account_hash: Vec<u8> },
+    PatientProcedures { account_hash: Vec<u8> },
+    PublicRecordIndex { index_hash: Vec<u8> },
+    StagedPublicRecords { group_hash: Vec<u8> },
+    ConditionDistribution { condition_hash: Vec<u8> },
+}
+
+/// Maximum number of public records returned by one `get_public_records` call
+const MAX_PUBLIC_RECORDS_PAGE: u64 = 100;
+
+/// Storage key of the version of the contract state layout.
+/// It is absent from the state written by the first version of the contract.
+pub(crate) const STATE_VERSION_KEY: &[u8] = b"STATE_VERSION";
+
+/// Versions of the contract state layout, read by `migrate` to convert the previous state
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) enum StateVersion {
+    V1,
+    V2,
+    V3,
+}
 
 // Define the contract
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Contract {
-    patients: LookupMap<AccountId, Patient>,
-    public_records: Vector<PublicRecord>,
+    patients: LookupMap<AccountId, VersionedPatient>,
+    patient_records: LookupMap<AccountId, PatientRecords>,
+    providers: LookupMap<AccountId, Provider>,
+    verified_providers: LookupSet<AccountId>,
+    used_signature_nonces: LookupSet<(AccountId, u64)>,
+    signature_nonces: LookupMap<u64, u64>,
+    record_signing_keys: LookupMap<u64, PublicKey>,
+    access_grants: LookupMap<AccountId, Vec<AccessGrant>>,
+    owner_id: AccountId,
+    admins: UnorderedSet<AccountId>,
+    emergency_accesses: LookupMap<AccountId, Vec<EmergencyAccess>>,
+    emergency_usage: LookupMap<AccountId, Vec<u64>>,
+    emergency_suspended: LookupSet<AccountId>,
+    audit_logs: LookupMap<AccountId, Vector<AuditEntry>>,
+    guardianships: LookupMap<AccountId, Guardianship>,
+    pending_guardianships: LookupMap<AccountId, Vec<PendingGuardianship>>,
+    pending_records: LookupMap<AccountId, Vec<PendingRecord>>,
+    next_pending_id: u64,
+    next_record_id: u64,
+    record_history: LookupMap<u64, Vec<RecordVersion>>,
+    retracted_records: LookupMap<AccountId, Vec<Tombstone>>,
+    public_records: Vector<VersionedPublicRecord>,
+    public_record_indices: LookupMap<PublicRecordIndex, Vector<u64>>,
+    public_records_by_date: TreeMap<(Date, u64), ()>,
+    age_generalization: AgeGeneralization,
+    k_anonymity: u64,
+    staged_public_records: UnorderedMap<QuasiIdentifier, Vector<PublicRecord>>,
+    total_staged_public_records: u64,
+    public_record_group_sizes: LookupMap<QuasiIdentifier, u64>,
+    public_record_commitments: LookupMap<Vec<u8>, PublicRecordLocation>,
+    withdrawn_public_records: Vector<u64>,
+    public_record_counts: LookupMap<PublicRecordCounter, u64>,
+    condition_distributions: LookupMap<String, UnorderedMap<(Gender, GeneralizedAge), u64>>,
+    storage_accounts: LookupMap<AccountId, StorageAccount>,
 }
 
 #[near_bindgen]
 impl Contract {
-    /// Initialize the contract
+    /// Initialize the contract.
+    /// The initializing account becomes the owner and the first admin.
 
     #[init]
     pub fn new() -> Self {
         assert!(!env::state_exists(), "Already initialized");
+        env::storage_write(STATE_VERSION_KEY, &StateVersion::V3.try_to_vec().unwrap());
         log!("Contract done");
-        Self {
-            patients: LookupMap::new(b"patients".to_vec()),
-            public_records: Vector::new(b"public_records".to_vec()),
-        }
+        Self::initial_state(env::predecessor_account_id())
     }
 
     /// Add a new patient to the contract with the provided
-    /// medical information and personal details
+    /// medical information and personal details.
+    /// The attached deposit is added to the patient's storage balance.
+    #[payable]
     pub fn add_patient(
         &mut self,
         full_name: String,
         birthday: String,
-        gender: String,
-        blood_type: String,
+        gender: Gender,
+        blood_type: BloodType,
     ) {
         let account_id = env::predecessor_account_id();
-
-        require!(
-            !self.patients.contains_key(&account_id),
-            "Patient already exists."
-        );
-
-        let patient = Patient::new(
+        if env::attached_deposit() > 0 {
+            self.internal_storage_deposit(&account_id, env::attached_deposit());
+        }
+        self.internal_add_patient(
+            account_id,
             full_name,
             birthday,
             gender,
             blood_type,
-            vec![],
-            vec![],
-            vec![],
-            vec![],
+            "add_patient",
         );
-
-        self.patients.insert(&account_id, &patient);
-        log!("Registered patient successfully. ID: {}", account_id);
     }
 
     /// Get patient information for the calling account
     pub fn get_patient(&self) -> Patient {
         let account_id = env::predecessor_account_id();
-        require!(
-            self.patients.contains_key(&account_id),
-            "Patient not found."
-        );
-
-        // We can safely unwrap here because we know the key exists
-        self.patients.get(&account_id).unwrap()
-    }
-
-    // WORKAROUND SOLUTION TO FETCH PATIENT INFORMATION
-    pub fn get_patient_workaround(&self, account_id: &AccountId) -> Patient {
-        // let account_id = env::predecessor_account_id();
-        require!(self.patients.contains_key(account_id), "Patient not found.");
+        let demographics = self.internal_demographics(&account_id);
 
-        // We can safely unwrap here because we know the key exists
-        self.patients.get(account_id).unwrap()
+        self.internal_patient_records(&account_id)
+            .patient(&demographics)
     }
 
     pub fn my_account(&self) -> AccountId {
@@ -93,28 +164,328 @@ impl Contract {
     }
 
     /// Get all public records stored on-chain.
+    /// Exceeds the view gas limit on large datasets, which are read with `get_public_records`.
     pub fn get_all_public_records(&self) -> Vec<PublicRecord> {
-        self.public_records.to_vec()
+        self.public_records
+            .iter()
+            .filter_map(|record| self.current_public_record(record))
+            .collect()
     }
 
-    /// Remove the patient and their records from the contract storage.
-    pub fn remove_patient(&mut self) {
-        let account_id = env::predecessor_account_id();
-        require!(
-            self.patients.contains_key(&account_id),
-            "Patient not found."
+    /// Get a page of the public records, oldest first.
+    /// At most `MAX_PUBLIC_RECORDS_PAGE` records are returned, skipping withdrawn records.
+    pub fn get_public_records(&self, from_index: u64, limit: u64) -> Vec<PublicRecord> {
+        let to_index = std::cmp::min(
+            from_index.saturating_add(std::cmp::min(limit, MAX_PUBLIC_RECORDS_PAGE)),
+            self.public_records.len(),
         );
-        log!("Removing patient with ID: {}", account_id);
+        (from_index..to_index)
+            .filter_map(|index| self.public_records.get(index))
+            .filter_map(|record| self.current_public_record(record))
+            .collect()
+    }
 
-        // Remove patient from the contract storage
-        self.patients.remove(&account_id);
+    /// Get the number of public records, including withdrawn records
+    pub fn public_records_count(&self) -> u64 {
+        self.public_records.len()
+    }
 
-        log!("Patient with ID: {} has been removed.", account_id);
+    /// Remove the patient and their records from the contract storage,
+    /// refunding the storage deposit released by the removal.
+    pub fn remove_patient(&mut self) {
+        let account_id = env::predecessor_account_id();
+        self.internal_discard_pending_records(&account_id);
+        let initial_storage = env::storage_usage();
+        self.internal_remove_patient(&account_id);
+        self.charge_storage(&account_id, initial_storage);
+
+        // Refund the storage freed by the removal
+        let freed_bytes = initial_storage.saturating_sub(env::storage_usage());
+        if self.storage_accounts.contains_key(&account_id) {
+            self.internal_storage_refund(
+                &account_id,
+                Balance::from(freed_bytes) * env::storage_byte_cost(),
+            );
+        }
     }
 
     /// Update the name of the patient
     pub fn full_name(&mut self, full_name: String) {
         let account_id = env::predecessor_account_id();
+        self.internal_full_name(account_id, full_name, "full_name");
+    }
+
+    /// Update the birthday of the patient
+    pub fn birthday(&mut self, birthday: String) {
+        let account_id = env::predecessor_account_id();
+        self.internal_birthday(account_id, birthday, "birthday");
+    }
+
+    /// Update the gender of the patient
+    pub fn gender(&mut self, gender: Gender) {
+        let account_id = env::predecessor_account_id();
+        self.internal_gender(account_id, gender, "gender");
+    }
+
+    /// Update the blood type of the patient
+    pub fn blood_type(&mut self, blood_type: BloodType) {
+        let account_id = env::predecessor_account_id();
+        self.internal_blood_type(account_id, blood_type, "blood_type");
+    }
+
+    /// Add a new allergy for the calling account. Returns the ID of the allergy.
+    pub fn add_allergy(&mut self, allergen: String, severity: AllergySeverity) -> u64 {
+        let account_id = env::predecessor_account_id();
+        self.internal_add_allergy(account_id, allergen, severity, "add_allergy")
+    }
+
+    /// Add a new immunization for the calling account. Returns the ID of the immunization.
+    pub fn add_immunization(&mut self, name: String, date: String) -> u64 {
+        let account_id = env::predecessor_account_id();
+        self.internal_add_immunization(account_id, name, date, "add_immunization")
+    }
+
+    /// Add a new procedure for the calling account. Returns the ID of the procedure.
+    pub fn add_procedure(&mut self, name: String, date: String, description: String) -> u64 {
+        let account_id = env::predecessor_account_id();
+        self.internal_add_procedure(account_id, name, date, description, "add_procedure")
+    }
+
+    /// Add a new medical record for the calling account. Returns the ID of the record.
+    pub fn add_medical_record(
+        &mut self,
+        condition: String,
+        record_data: String,
+        date: String,
+    ) -> u64 {
+        let account_id = env::predecessor_account_id();
+        self.internal_add_medical_record(
+            account_id,
+            condition,
+            record_data,
+            date,
+            "add_medical_record",
+        )
+    }
+
+    /// Add a new PUBLIC medical record to the calling account's medical records and
+    /// to the public records, carrying the given contributor commitment.
+    /// Returns the ID of the record.
+    pub fn add_public_medical_record(
+        &mut self,
+        condition: String,
+        record_data: String,
+        date: String,
+        contributor_commitment: Base64VecU8,
+    ) -> u64 {
+        let account_id = env::predecessor_account_id();
+        self.internal_add_public_medical_record(
+            account_id,
+            condition,
+            record_data,
+            date,
+            contributor_commitment,
+            "add_public_medical_record",
+        )
+    }
+
+    /// Add a new treatment for the calling account. A public treatment requires
+    /// a contributor commitment. Returns the ID of the treatment.
+    pub fn add_treatment(
+        &mut self,
+        treatment: String,
+        dosage: String,
+        start_date: String,
+        finish_date: String,
+        reason: Option<String>,
+        public: bool,
+        contributor_commitment: Option<Base64VecU8>,
+    ) -> u64 {
+        let account_id = env::predecessor_account_id();
+        self.internal_add_treatment(
+            account_id,
+            treatment,
+            dosage,
+            start_date,
+            finish_date,
+            reason,
+            public,
+            contributor_commitment,
+            "add_treatment",
+        )
+    }
+
+    /// Add a new family medical record for the calling account. A public record
+    /// requires a contributor commitment. Returns the ID of the record.
+    pub fn add_family_medical_record(
+        &mut self,
+        condition: String,
+        relation_to_patient: String,
+        public: bool,
+        contributor_commitment: Option<Base64VecU8>,
+    ) -> u64 {
+        let account_id = env::predecessor_account_id();
+        self.internal_add_family_medical_record(
+            account_id,
+            condition,
+            relation_to_patient,
+            public,
+            contributor_commitment,
+            "add_family_medical_record",
+        )
+    }
+
+    /// Transfer the attached deposit to the contract developers, dividing it equally.
+    pub fn transfer_to_developers(&mut self) {
+        let attached_deposit = env::attached_deposit();
+
+        // The developers of the contract.
+        // FIXME: Replace with the actual developers of the contract.
+        let developers: Vec<AccountId> = vec![
+            "tarek.near".parse().unwrap(),
+            "prakhar.near".parse().unwrap(),
+            "yulduz.near".parse().unwrap(),
+        ];
+
+        // Calculate the split amount for each developer.
+        let split_amount = attached_deposit / (developers.len() as u128);
+
+        // Transfer the split amount to each developer.
+        for developer_account_id in &developers {
+            Promise::new(developer_account_id.clone()).transfer(split_amount);
+        }
+        Event::Payout {
+            recipients: &developers,
+            amount: U128(split_amount),
+        }
+        .emit();
+    }
+}
+
+impl Contract {
+    /// Create the empty state of the contract, with the given account
+    /// as its owner and first admin
+    fn initial_state(owner_id: AccountId) -> Self {
+        let mut admins = UnorderedSet::new(b"admins".to_vec());
+        admins.insert(&owner_id);
+        Self::state_with_admins(owner_id, admins)
+    }
+
+    /// Create the empty state of the contract with the given owner and admins
+    fn state_with_admins(owner_id: AccountId, admins: UnorderedSet<AccountId>) -> Self {
+        Self {
+            patients: LookupMap::new(b"patients".to_vec()),
+            patient_records: LookupMap::new(b"patient_records".to_vec()),
+            providers: LookupMap::new(b"providers".to_vec()),
+            verified_providers: LookupSet::new(b"verified_providers".to_vec()),
+            used_signature_nonces: LookupSet::new(b"used_signature_nonces".to_vec()),
+            signature_nonces: LookupMap::new(b"signature_nonces".to_vec()),
+            record_signing_keys: LookupMap::new(b"record_signing_keys".to_vec()),
+            access_grants: LookupMap::new(b"access_grants".to_vec()),
+            owner_id,
+            admins,
+            emergency_accesses: LookupMap::new(b"emergency_accesses".to_vec()),
+            emergency_usage: LookupMap::new(b"emergency_usage".to_vec()),
+            emergency_suspended: LookupSet::new(b"emergency_suspended".to_vec()),
+            audit_logs: LookupMap::new(b"audit_logs".to_vec()),
+            guardianships: LookupMap::new(b"guardianships".to_vec()),
+            pending_guardianships: LookupMap::new(b"pending_guardianships".to_vec()),
+            pending_records: LookupMap::new(b"pending_records".to_vec()),
+            next_pending_id: 0,
+            next_record_id: 0,
+            record_history: LookupMap::new(b"record_history".to_vec()),
+            retracted_records: LookupMap::new(b"retracted_records".to_vec()),
+            public_records: Vector::new(b"public_records".to_vec()),
+            public_record_indices: LookupMap::new(b"public_record_indices".to_vec()),
+            public_records_by_date: TreeMap::new(b"public_records_by_date".to_vec()),
+            age_generalization: AgeGeneralization::default(),
+            // Records are published immediately until the owner raises k
+            k_anonymity: 1,
+            staged_public_records: UnorderedMap::new(b"staged_public_records".to_vec()),
+            total_staged_public_records: 0,
+            public_record_group_sizes: LookupMap::new(b"public_record_group_sizes".to_vec()),
+            public_record_commitments: LookupMap::new(b"public_record_commitments".to_vec()),
+            withdrawn_public_records: Vector::new(b"withdrawn_public_records".to_vec()),
+            public_record_counts: LookupMap::new(b"public_record_counts".to_vec()),
+            condition_distributions: LookupMap::new(b"condition_distributions".to_vec()),
+            storage_accounts: LookupMap::new(b"storage_accounts".to_vec()),
+        }
+    }
+
+    /// Remove the given patient and their records from the contract storage.
+    /// The audit log and the emergency access log are kept.
+    fn internal_remove_patient(&mut self, account_id: &AccountId) {
+        require!(self.patients.contains_key(account_id), "Patient not found.");
+        log!("Removing patient with ID: {}", account_id);
+
+        // Remove the version history of the patient's entries, including retracted ones
+        let mut records = self.internal_patient_records(account_id);
+        let tombstones = self
+            .retracted_records
+            .remove(account_id)
+            .unwrap_or_default();
+        for entry in records
+            .entries()
+            .into_iter()
+            .chain(tombstones.iter().map(Tombstone::record))
+        {
+            self.record_history.remove(&entry.id());
+        }
+
+        // Remove patient, their records and the access they granted from the contract storage
+        records.clear();
+        self.patient_records.remove(account_id);
+        self.patients.remove(account_id);
+        self.access_grants.remove(account_id);
+        self.guardianships.remove(account_id);
+        self.pending_records.remove(account_id);
+        self.record_access(account_id, "remove_patient", AccessScope::all());
+        Event::PatientRemoved {
+            patient_id: account_id,
+        }
+        .emit();
+
+        log!("Patient with ID: {} has been removed.", account_id);
+    }
+
+    /// Add a new patient to the contract for the given account
+    fn internal_add_patient(
+        &mut self,
+        account_id: AccountId,
+        full_name: String,
+        birthday: String,
+        gender: Gender,
+        blood_type: BloodType,
+        method: &str,
+    ) {
+        let initial_storage = env::storage_usage();
+        require!(
+            !self.patients.contains_key(&account_id),
+            "Patient already exists."
+        );
+
+        let demographics = Demographics::new(
+            full_name,
+            parse_date("birthday", &birthday),
+            gender,
+            blood_type,
+        );
+
+        self.patients.insert(&account_id, &demographics.into());
+        self.patient_records
+            .insert(&account_id, &PatientRecords::new(&account_id));
+        self.record_access(&account_id, method, vec![AccessScope::Demographics]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::PatientRegistered {
+            patient_id: &account_id,
+        }
+        .emit();
+        log!("Registered patient successfully. ID: {}", account_id);
+    }
+
+    /// Update the name of the given patient
+    fn internal_full_name(&mut self, account_id: AccountId, full_name: String, method: &str) {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Patient not found."
@@ -122,19 +493,26 @@ impl Contract {
         log!("Updating name for patient with ID: {}", account_id);
         log!("New full name: {}", full_name);
 
-        let mut patient = self.patients.get(&account_id).expect("Patient not found.");
+        let mut demographics = self.internal_demographics(&account_id);
 
         // Update the patient's full name
-        patient.update_full_name(full_name);
+        demographics.update_full_name(full_name);
 
         // Update the patient in the contract storage
-        self.patients.insert(&account_id, &patient);
+        self.patients.insert(&account_id, &demographics.into());
+        self.record_access(&account_id, method, vec![AccessScope::Demographics]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::DemographicsUpdated {
+            patient_id: &account_id,
+            field: "full_name",
+        }
+        .emit();
         log!("Updated name for patient with ID: {}", account_id);
     }
 
-    /// Update the birthday of the patient
-    pub fn birthday(&mut self, birthday: String) {
-        let account_id = env::predecessor_account_id();
+    /// Update the birthday of the given patient
+    fn internal_birthday(&mut self, account_id: AccountId, birthday: String, method: &str) {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Patient not found."
@@ -142,19 +520,26 @@ impl Contract {
         log!("Updating birthday for patient with ID: {}", account_id);
         log!("New birthday: {}", birthday);
 
-        let mut patient = self.patients.get(&account_id).expect("Patient not found.");
+        let mut demographics = self.internal_demographics(&account_id);
 
         // Update the patient's birthday
-        patient.update_birthday(birthday);
+        demographics.update_birthday(parse_date("birthday", &birthday));
 
         // Update the patient in the contract storage
-        self.patients.insert(&account_id, &patient);
+        self.patients.insert(&account_id, &demographics.into());
+        self.record_access(&account_id, method, vec![AccessScope::Demographics]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::DemographicsUpdated {
+            patient_id: &account_id,
+            field: "birthday",
+        }
+        .emit();
         log!("Updated birthday for patient with ID: {}", account_id);
     }
 
-    /// Update the gender of the patient
-    pub fn gender(&mut self, gender: String) {
-        let account_id = env::predecessor_account_id();
+    /// Update the gender of the given patient
+    fn internal_gender(&mut self, account_id: AccountId, gender: Gender, method: &str) {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Patient not found."
@@ -162,19 +547,26 @@ impl Contract {
         log!("Updating gender for patient with ID: {}", account_id);
         log!("New gender: {}", gender);
 
-        let mut patient = self.patients.get(&account_id).expect("Patient not found.");
+        let mut demographics = self.internal_demographics(&account_id);
 
         // Update the patient's gender
-        patient.update_gender(gender);
+        demographics.update_gender(gender);
 
         // Update the patient in the contract storage
-        self.patients.insert(&account_id, &patient);
+        self.patients.insert(&account_id, &demographics.into());
+        self.record_access(&account_id, method, vec![AccessScope::Demographics]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::DemographicsUpdated {
+            patient_id: &account_id,
+            field: "gender",
+        }
+        .emit();
         log!("Updated gender for patient with ID: {}", account_id);
     }
 
-    /// Update the blood type of the patient
-    pub fn blood_type(&mut self, blood_type: String) {
-        let account_id = env::predecessor_account_id();
+    /// Update the blood type of the given patient
+    fn internal_blood_type(&mut self, account_id: AccountId, blood_type: BloodType, method: &str) {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Patient not found."
@@ -182,19 +574,32 @@ impl Contract {
         log!("Updating blood type for patient with ID: {}", account_id);
         log!("New blood type: {}", blood_type);
 
-        let mut patient = self.patients.get(&account_id).expect("Patient not found.");
+        let mut demographics = self.internal_demographics(&account_id);
 
         // Update the patient's blood type
-        patient.update_blood_type(blood_type);
+        demographics.update_blood_type(blood_type);
 
         // Update the patient in the contract storage
-        self.patients.insert(&account_id, &patient);
+        self.patients.insert(&account_id, &demographics.into());
+        self.record_access(&account_id, method, vec![AccessScope::Demographics]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::DemographicsUpdated {
+            patient_id: &account_id,
+            field: "blood_type",
+        }
+        .emit();
         log!("Updated blood type for patient with ID: {}", account_id);
     }
 
-    /// Add a new allergy for the calling account
-    pub fn add_allergy(&mut self, allergen: String, severity: String) {
-        let account_id = env::predecessor_account_id();
+    /// Add a new allergy for the given patient
+    fn internal_add_allergy(
+        &mut self,
+        account_id: AccountId,
+        allergen: String,
+        severity: AllergySeverity,
+        method: &str,
+    ) -> u64 {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Patient not found."
@@ -203,22 +608,37 @@ impl Contract {
         log!("Allergen: {}", allergen);
         log!("Severity: {}", severity);
 
-        let mut patient = self.patients.get(&account_id).expect("Patient not found.");
-
         // Create a new allergy
-        let allergy = Allergy::new(allergen, severity);
-
-        // Update the patient's allergies vector
-        patient.add_allergy(allergy);
-
-        // Update the patient in the contract storage
-        self.patients.insert(&account_id, &patient);
+        let id = self.assign_record_id();
+        let allergy = Allergy::new(id, allergen, severity);
+
+        // Add the allergy to the patient's allergies
+        let mut records = self.internal_patient_records(&account_id);
+        records.add_allergy(allergy);
+
+        // Update the patient's collections in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_access(&account_id, method, vec![AccessScope::Allergies]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAdded {
+            patient_id: &account_id,
+            record_id: id,
+            section: AccessScope::Allergies,
+        }
+        .emit();
         log!("Added allergy for patient with ID: {}", account_id);
+        id
     }
 
-    /// Add a new immunization for the calling account
-    pub fn add_immunization(&mut self, name: String, date: String) {
-        let account_id = env::predecessor_account_id();
+    /// Add a new immunization for the given patient
+    fn internal_add_immunization(
+        &mut self,
+        account_id: AccountId,
+        name: String,
+        date: String,
+        method: &str,
+    ) -> u64 {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Patient not found."
@@ -227,22 +647,38 @@ impl Contract {
         log!("Name: {}", name);
         log!("Date: {}", date);
 
-        let mut patient = self.patients.get(&account_id).expect("Patient not found.");
-
         // Create a new immunization
-        let immunization = Immunization::new(name, date);
-
-        // Update the patient's immunizations vector
-        patient.add_immunization(immunization);
-
-        // Update the patient in the contract storage
-        self.patients.insert(&account_id, &patient);
+        let id = self.assign_record_id();
+        let immunization = Immunization::new(id, name, parse_date("date", &date));
+
+        // Add the immunization to the patient's immunizations
+        let mut records = self.internal_patient_records(&account_id);
+        records.add_immunization(immunization);
+
+        // Update the patient's collections in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_access(&account_id, method, vec![AccessScope::Immunizations]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAdded {
+            patient_id: &account_id,
+            record_id: id,
+            section: AccessScope::Immunizations,
+        }
+        .emit();
         log!("Added immunization for patient with ID: {}", account_id);
+        id
     }
 
-    /// Add a new procedure for the calling account
-    pub fn add_procedure(&mut self, name: String, date: String, description: String) {
-        let account_id = env::predecessor_account_id();
+    /// Add a new procedure for the given patient
+    fn internal_add_procedure(
+        &mut self,
+        account_id: AccountId,
+        name: String,
+        date: String,
+        description: String,
+        method: &str,
+    ) -> u64 {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Patient not found."
@@ -252,107 +688,143 @@ impl Contract {
         log!("Date: {}", date);
         log!("Description: {}", description);
 
-        let mut patient = self.patients.get(&account_id).expect("Patient not found.");
-
         // Create a new procedure
-        let procedure = Procedure::new(name, date, description);
-
-        // Update the patient's procedures vector
-        patient.add_procedure(procedure);
-
-        // Update the patient in the contract storage
-        self.patients.insert(&account_id, &patient);
+        let id = self.assign_record_id();
+        let procedure = Procedure::new(id, name, parse_date("date", &date), description);
+
+        // Add the procedure to the patient's procedures
+        let mut records = self.internal_patient_records(&account_id);
+        records.add_procedure(procedure);
+
+        // Update the patient's collections in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_access(&account_id, method, vec![AccessScope::Procedures]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAdded {
+            patient_id: &account_id,
+            record_id: id,
+            section: AccessScope::Procedures,
+        }
+        .emit();
         log!("Added procedure for patient with ID: {}", account_id);
+        id
     }
 
-    /// Add a new medical record for the calling account
-    pub fn add_medical_record(
+    /// Add a new medical record for the given patient
+    fn internal_add_medical_record(
         &mut self,
-        id: u64,
+        account_id: AccountId,
         condition: String,
         record_data: String,
         date: String,
-    ) {
-        let account_id = env::predecessor_account_id();
+        method: &str,
+    ) -> u64 {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Patient not found."
         );
         log!("Adding medical record for patient with ID: {}", account_id);
-        log!("ID: {}", id);
         log!("Condition: {}", condition);
         log!("Record Data: {}", record_data);
         log!("Date: {}", date);
 
-        let mut patient = self.patients.get(&account_id).expect("Patient not found.");
-
         // Create a new medical record
-        let medical_record = MedicalRecord::new(id, condition, record_data, date);
-
-        // Update the patient's medical records vector
-        patient.add_medical_record(medical_record);
-
-        // Update the patient in the contract storage
-        self.patients.insert(&account_id, &patient);
+        let id = self.assign_record_id();
+        let medical_record =
+            MedicalRecord::new(id, condition, record_data, parse_date("date", &date));
+
+        // Add the medical record to the patient's medical records
+        let mut records = self.internal_patient_records(&account_id);
+        records.add_medical_record(medical_record);
+
+        // Update the patient's collections in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_access(&account_id, method, vec![AccessScope::MedicalRecords]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAdded {
+            patient_id: &account_id,
+            record_id: id,
+            section: AccessScope::MedicalRecords,
+        }
+        .emit();
         log!("Added medical record for patient with ID: {}", account_id);
+        id
     }
 
     /// Add a new PUBLIC medical record
-    pub fn add_public_medical_record(
+    fn internal_add_public_medical_record(
         &mut self,
-        id: u64,
+        account_id: AccountId,
         condition: String,
         record_data: String,
         date: String,
-    ) {
-        let account_id = env::predecessor_account_id();
+        contributor_commitment: Base64VecU8,
+        method: &str,
+    ) -> u64 {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Only registered patients can add public medical records."
         );
-        log!("ID: {}", id);
         log!("Condition: {}", condition);
         log!("Record Data: {}", record_data);
         log!("Date: {}", date);
 
         // Retrieve patient information.
-        // We only need this to get the patient's birthday and gender
+        // We only need this to get the patient's generalized age and gender
         // (required for the PublicRecord struct)
-        let patient = self.patients.get(&account_id).expect("Patient not found.");
+        let demographics = self.internal_demographics(&account_id);
 
         // Create a new medical record
-        let medical_record = MedicalRecord::new(id, condition, record_data, date);
+        let id = self.assign_record_id();
+        let medical_record =
+            MedicalRecord::new(id, condition, record_data, parse_date("date", &date));
 
         // Add the medical record to the public records
-        let public_medical_record = PublicRecord::new(
+        self.internal_publish_record(
             RecordType::MedicalRecord(medical_record.clone()),
-            patient.birthday(),
-            patient.gender(),
+            &demographics,
+            Some(contributor_commitment),
         );
-        self.public_records.push(&public_medical_record);
 
-        log!("Added a new public medical record");
+        // Add the medical record to the patient's medical records
+        let mut records = self.internal_patient_records(&account_id);
+        records.add_medical_record(medical_record);
+
+        // Update the patient's collections in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_access(&account_id, method, vec![AccessScope::MedicalRecords]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAdded {
+            patient_id: &account_id,
+            record_id: id,
+            section: AccessScope::MedicalRecords,
+        }
+        .emit();
+        log!("Added a new public medical record with ID: {}", id);
+        id
     }
 
-    /// Add a new treatment for the calling account
-    #[allow(clippy::too_many_arguments)]
-    pub fn add_treatment(
+    /// Add a new treatment for the given patient
+    fn internal_add_treatment(
         &mut self,
-        id: u64,
+        account_id: AccountId,
         treatment: String,
         dosage: String,
         start_date: String,
         finish_date: String,
         reason: Option<String>,
         public: bool,
-    ) {
-        let account_id = env::predecessor_account_id();
+        contributor_commitment: Option<Base64VecU8>,
+        method: &str,
+    ) -> u64 {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Patient not found."
         );
         log!("Adding treatment for patient with ID: {}", account_id);
-        log!("ID: {}", id);
         log!("Treatment: {}", treatment);
         log!("Dosage: {}", dosage);
         log!("Start Date: {}", start_date);
@@ -363,38 +835,51 @@ impl Contract {
             log!("Reason: None");
         }
 
-        let mut patient = self.patients.get(&account_id).expect("Patient not found.");
+        let demographics = self.internal_demographics(&account_id);
 
         // Create a new treatment
+        let (start_date, finish_date) = parse_treatment_period(&start_date, &finish_date);
+        let id = self.assign_record_id();
         let treatment = Treatment::new(id, treatment, dosage, start_date, finish_date, reason);
 
         // Add the treatment to the public records if public is true
         if public {
-            let public_treatment = PublicRecord::new(
+            self.internal_publish_record(
                 RecordType::Treatment(treatment.clone()),
-                patient.birthday(),
-                patient.gender(),
+                &demographics,
+                contributor_commitment,
             );
-            self.public_records.push(&public_treatment);
         }
 
-        // Update the patient's treatments vector
-        patient.add_treatment(treatment);
-
-        // Update the patient in the contract storage
-        self.patients.insert(&account_id, &patient);
+        // Add the treatment to the patient's treatments
+        let mut records = self.internal_patient_records(&account_id);
+        records.add_treatment(treatment);
+
+        // Update the patient's collections in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_access(&account_id, method, vec![AccessScope::Treatments]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAdded {
+            patient_id: &account_id,
+            record_id: id,
+            section: AccessScope::Treatments,
+        }
+        .emit();
         log!("Added treatment for patient with ID: {}", account_id);
+        id
     }
 
-    /// Add a new family medical record for the calling account
-    pub fn add_family_medical_record(
+    /// Add a new family medical record for the given patient
+    fn internal_add_family_medical_record(
         &mut self,
-        id: u64,
+        account_id: AccountId,
         condition: String,
         relation_to_patient: String,
         public: bool,
-    ) {
-        let account_id = env::predecessor_account_id();
+        contributor_commitment: Option<Base64VecU8>,
+        method: &str,
+    ) -> u64 {
+        let initial_storage = env::storage_usage();
         require!(
             self.patients.contains_key(&account_id),
             "Patient not found."
@@ -403,54 +888,135 @@ impl Contract {
             "Adding family medical record for patient with ID: {}",
             account_id
         );
-        log!("ID: {}", id);
         log!("Condition: {}", condition);
         log!("Relation to Patient: {}", relation_to_patient);
 
-        let mut patient = self.patients.get(&account_id).expect("Patient not found.");
+        let demographics = self.internal_demographics(&account_id);
 
         // Create a new family medical record
+        let id = self.assign_record_id();
         let family_medical_record = FamilyMedicalRecord::new(id, condition, relation_to_patient);
 
         // Add the family medical record to the public records if public is true
         if public {
-            let public_family_medical_record = PublicRecord::new(
+            self.internal_publish_record(
                 RecordType::FamilyMedicalRecord(family_medical_record.clone()),
-                patient.birthday(),
-                patient.gender(),
+                &demographics,
+                contributor_commitment,
             );
-            self.public_records.push(&public_family_medical_record);
         }
 
-        // Update the patient's family medical records vector
-        patient.add_family_medical_record(family_medical_record);
-
-        // Update the patient in the contract storage
-        self.patients.insert(&account_id, &patient);
+        // Add the family medical record to the patient's family medical records
+        let mut records = self.internal_patient_records(&account_id);
+        records.add_family_medical_record(family_medical_record);
+
+        // Update the patient's collections in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_access(&account_id, method, vec![AccessScope::FamilyMedicalRecords]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAdded {
+            patient_id: &account_id,
+            record_id: id,
+            section: AccessScope::FamilyMedicalRecords,
+        }
+        .emit();
         log!(
             "Added family medical record for patient with ID: {}",
             account_id
         );
+        id
     }
 
-    /// Transfer the attached deposit to the contract developers, dividing it equally.
-    pub fn transfer_to_developers(&mut self) {
-        let attached_deposit = env::attached_deposit();
+    /// Publish an anonymized record, generalizing the patient's age at the
+    /// record's date, or at the publication date for records without one.
+    /// The record is staged until it can be released without breaking k-anonymity.
+    fn internal_publish_record(
+        &mut self,
+        record_type: RecordType,
+        demographics: &Demographics,
+        contributor_commitment: Option<Base64VecU8>,
+    ) {
+        let contributor_commitment = contributor_commitment
+            .expect("A contributor commitment is required to publish a record.");
+        self.assert_unused_commitment(&contributor_commitment);
+
+        let on = record_type.date().unwrap_or_else(today);
+        let age = self
+            .age_generalization
+            .generalize(demographics.birthday(), on);
+        let record = PublicRecord::new(
+            record_type,
+            age,
+            demographics.gender(),
+            Some(contributor_commitment),
+        );
+        self.internal_stage_public_record(record);
+    }
 
-        // The developers of the contract.
-        // FIXME: Replace with the actual developers of the contract.
-        let developers: Vec<AccountId> = vec![
-            "tarek.near".parse().unwrap(),
-            "prakhar.near".parse().unwrap(),
-            "yulduz.near".parse().unwrap(),
-        ];
+    /// Append the record to the public records and to the secondary indices
+    fn internal_release_public_record(&mut self, record: PublicRecord) {
+        let index = self.public_records.len();
+        self.public_records.push(&record.clone().into());
+        self.index_public_record(index, &record);
+        if let Some(contributor) = record.contributor() {
+            self.public_record_commitments
+                .insert(&contributor.into(), &PublicRecordLocation::Released(index));
+        }
+        Event::PublicRecordPublished {
+            index,
+            section: record.record_type().scope(),
+        }
+        .emit();
+    }
 
-        // Calculate the split amount for each developer.
-        let split_amount = attached_deposit / (developers.len() as u128);
+    /// Convert a stored public record to the current layout, unless it was withdrawn
+    fn current_public_record(&self, record: VersionedPublicRecord) -> Option<PublicRecord> {
+        record.into_current(self.age_generalization, today())
+    }
 
-        // Transfer the split amount to each developer.
-        for developer_account_id in developers {
-            Promise::new(developer_account_id.clone()).transfer(split_amount);
-        }
+    /// Get the demographics of the given patient, in the current layout
+    fn internal_demographics(&self, account_id: &AccountId) -> Demographics {
+        self.patients
+            .get(account_id)
+            .map(VersionedPatient::into_current)
+            .expect("Patient not found.")
+    }
+
+    /// Get the collections holding the records of the given patient
+    fn internal_patient_records(&self, account_id: &AccountId) -> PatientRecords {
+        self.patient_records
+            .get(account_id)
+            .expect("Patient not found.")
     }
+
+    /// Assign a new record ID, unique across all patients and record kinds
+    fn assign_record_id(&mut self) -> u64 {
+        let id = self.next_record_id;
+        self.next_record_id += 1;
+        id
+    }
+}
+
+/// Parse an ISO-8601 date passed to a contract method,
+/// panicking with a message naming the offending argument
+pub(crate) fn parse_date(argument: &str, value: &str) -> Date {
+    value
+        .parse()
+        .unwrap_or_else(|err: String| panic!("Invalid {}: {}", argument, err))
+}
+
+/// Parse the start and finish dates of a treatment, checking they are in order
+pub(crate) fn parse_treatment_period(start_date: &str, finish_date: &str) -> (Date, Date) {
+    let start_date = parse_date("start_date", start_date);
+    let finish_date = parse_date("finish_date", finish_date);
+    require!(
+        start_date <= finish_date,
+        "Treatment cannot finish before it starts."
+    );
+    (start_date, finish_date)
+}
+
+/// Get the current UTC date of the block
+pub(crate) fn today() -> Date {
+    Date::from_timestamp(env::block_timestamp())
 }
@@ -0,0 +1,52 @@
+use super::*;
+use near_sdk::Gas;
+
+/// Gas attached to the `migrate` call made after deploying new code
+const GAS_FOR_MIGRATE: Gas = Gas(100_000_000_000_000);
+
+#[near_bindgen]
+impl Contract {
+    /// Get the account that owns the contract and can upgrade its code
+    pub fn get_owner(&self) -> AccountId {
+        self.owner_id.clone()
+    }
+
+    /// Make the given account the owner of the contract. Only callable by the owner.
+    pub fn transfer_ownership(&mut self, new_owner_id: AccountId) {
+        self.assert_owner();
+        require!(
+            new_owner_id != self.owner_id,
+            "Account is already the owner."
+        );
+        log!(
+            "Transferred ownership from {} to {}",
+            self.owner_id,
+            new_owner_id
+        );
+        self.owner_id = new_owner_id;
+    }
+
+    /// Deploy the wasm code passed as the raw input of the call, then convert the
+    /// state to the new code's layout by calling its `migrate` method in a follow-up
+    /// promise. A failed migration reverts the deployment. Only callable by the owner.
+    pub fn upgrade(&self) -> Promise {
+        self.assert_owner();
+        let code = env::input().expect("Contract code not found.");
+        require!(!code.is_empty(), "Contract code not found.");
+        log!("Upgrading contract code ({} bytes)", code.len());
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call("migrate".to_string(), vec![], 0, GAS_FOR_MIGRATE)
+    }
+}
+
+impl Contract {
+    /// Panic unless the calling account is the owner
+    pub fn assert_owner(&self) {
+        require!(
+            env::predecessor_account_id() == self.owner_id,
+            "Only the owner can call this method."
+        );
+    }
+}
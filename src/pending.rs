@@ -0,0 +1,319 @@
+use super::*;
+use crate::types::{ProviderRole, SubmittedRecord};
+use near_sdk::json_types::Base64VecU8;
+
+/// Provider roles allowed to author records for a patient
+const AUTHOR_ROLES: [ProviderRole; 5] = [
+    ProviderRole::Doctor,
+    ProviderRole::Nurse,
+    ProviderRole::Pharmacist,
+    ProviderRole::Lab,
+    ProviderRole::Emergency,
+];
+/// Time in nanoseconds after which a pending record expires (30 days)
+const PENDING_RECORD_EXPIRY: u64 = 30 * 24 * 3_600_000_000_000;
+
+#[near_bindgen]
+impl Contract {
+    /// Submit a medical record for a patient who granted the calling provider
+    /// access to their medical records. Returns the ID of the pending record.
+    /// An optional signature, with the nonce it was made with, is verified
+    /// against the provider's signing key.
+    pub fn submit_medical_record(
+        &mut self,
+        patient_id: AccountId,
+        condition: String,
+        record_data: String,
+        date: String,
+        signature: Option<Base64VecU8>,
+        signature_nonce: Option<u64>,
+    ) -> u64 {
+        let provider_id = env::predecessor_account_id();
+        let id = self.assign_record_id();
+        let mut record = MedicalRecord::new(id, condition, record_data, parse_date("date", &date))
+            .with_author(provider_id.clone());
+        let mut nonce = None;
+        if let Some(signature) = signature {
+            let signature_nonce = signature_nonce.expect("A signature nonce is required.");
+            record = record.with_signature(signature.into());
+            self.assert_record_signature(
+                &provider_id,
+                &record.signing_payload(&patient_id, signature_nonce),
+                record.signature(),
+            );
+            nonce = Some(signature_nonce);
+        }
+        self.internal_submit_record(
+            patient_id,
+            SubmittedRecord::MedicalRecord(record),
+            nonce,
+            "submit_medical_record",
+        )
+    }
+
+    /// Submit a treatment for a patient who granted the calling provider
+    /// access to their treatments. Returns the ID of the pending record.
+    /// An optional signature, with the nonce it was made with, is verified
+    /// against the provider's signing key.
+    pub fn submit_treatment(
+        &mut self,
+        patient_id: AccountId,
+        treatment: String,
+        dosage: String,
+        start_date: String,
+        finish_date: String,
+        reason: Option<String>,
+        signature: Option<Base64VecU8>,
+        signature_nonce: Option<u64>,
+    ) -> u64 {
+        let provider_id = env::predecessor_account_id();
+        let (start_date, finish_date) = parse_treatment_period(&start_date, &finish_date);
+        let id = self.assign_record_id();
+        let mut record = Treatment::new(id, treatment, dosage, start_date, finish_date, reason)
+            .with_author(provider_id.clone());
+        let mut nonce = None;
+        if let Some(signature) = signature {
+            let signature_nonce = signature_nonce.expect("A signature nonce is required.");
+            record = record.with_signature(signature.into());
+            self.assert_record_signature(
+                &provider_id,
+                &record.signing_payload(&patient_id, signature_nonce),
+                record.signature(),
+            );
+            nonce = Some(signature_nonce);
+        }
+        self.internal_submit_record(
+            patient_id,
+            SubmittedRecord::Treatment(record),
+            nonce,
+            "submit_treatment",
+        )
+    }
+
+    /// Submit a procedure for a patient who granted the calling provider
+    /// access to their procedures. Returns the ID of the pending record.
+    pub fn submit_procedure(
+        &mut self,
+        patient_id: AccountId,
+        name: String,
+        date: String,
+        description: String,
+    ) -> u64 {
+        let id = self.assign_record_id();
+        let record = Procedure::new(id, name, parse_date("date", &date), description)
+            .with_author(env::predecessor_account_id());
+        self.internal_submit_record(
+            patient_id,
+            SubmittedRecord::Procedure(record),
+            None,
+            "submit_procedure",
+        )
+    }
+
+    /// Get the records submitted by providers that await the calling patient's decision.
+    /// This is a change method, as view calls can't identify the caller.
+    pub fn get_pending_records(&mut self) -> Vec<PendingRecord> {
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.patients.contains_key(&account_id),
+            "Patient not found."
+        );
+
+        self.pending_records.get(&account_id).unwrap_or_default()
+    }
+
+    /// Accept a pending record into the calling patient's information.
+    /// The record keeps the submitting provider as its author. Its storage,
+    /// paid by the provider while pending, is charged to the patient.
+    pub fn accept_record(&mut self, pending_id: u64) {
+        let account_id = env::predecessor_account_id();
+        let mut records = self.internal_patient_records(&account_id);
+        let pending = self.internal_discard_pending_record(&account_id, pending_id, true);
+        require!(!is_expired(&pending), "Pending record has expired.");
+        let initial_storage = env::storage_usage();
+        log!(
+            "Accepting record {} from provider with ID: {} for patient with ID: {}",
+            pending_id,
+            pending.author(),
+            account_id
+        );
+
+        let record = pending.record();
+        let record_id = record.id();
+        let scope = record.scope();
+        match record {
+            SubmittedRecord::MedicalRecord(medical_record) => {
+                records.add_medical_record(medical_record)
+            }
+            SubmittedRecord::Treatment(treatment) => records.add_treatment(treatment),
+            SubmittedRecord::Procedure(procedure) => records.add_procedure(procedure),
+        }
+
+        // Update the patient's collections in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_access(&account_id, "accept_record", vec![scope]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAdded {
+            patient_id: &account_id,
+            record_id,
+            section: scope,
+        }
+        .emit();
+        log!(
+            "Accepted record {} for patient with ID: {}",
+            pending_id,
+            account_id
+        );
+    }
+
+    /// Reject a pending record, discarding it and releasing its storage to the provider
+    pub fn reject_record(&mut self, pending_id: u64) {
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.patients.contains_key(&account_id),
+            "Patient not found."
+        );
+
+        let pending = self.internal_discard_pending_record(&account_id, pending_id, false);
+        let initial_storage = env::storage_usage();
+        self.record_access(&account_id, "reject_record", vec![pending.record().scope()]);
+        self.charge_storage(&account_id, initial_storage);
+        log!(
+            "Rejected record {} for patient with ID: {}",
+            pending_id,
+            account_id
+        );
+    }
+
+    /// Discard a record the calling provider submitted that expired before
+    /// the patient decided on it, releasing its storage to the provider
+    pub fn reclaim_expired_record(&mut self, patient_id: AccountId, pending_id: u64) {
+        let provider_id = env::predecessor_account_id();
+        let pending = self
+            .pending_records
+            .get(&patient_id)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|record| record.id() == pending_id)
+            .expect("Pending record not found.");
+        require!(
+            pending.author() == provider_id,
+            "Only the submitting provider can reclaim the record."
+        );
+        require!(is_expired(&pending), "Pending record has not expired yet.");
+
+        self.internal_discard_pending_record(&patient_id, pending_id, false);
+        let initial_storage = env::storage_usage();
+        self.record_access(
+            &patient_id,
+            "reclaim_expired_record",
+            vec![pending.record().scope()],
+        );
+        self.charge_storage(&provider_id, initial_storage);
+        log!(
+            "Reclaimed expired record {} for patient with ID: {}",
+            pending_id,
+            patient_id
+        );
+    }
+}
+
+impl Contract {
+    /// Queue a record authored by the calling provider for the patient's acknowledgement,
+    /// using up the nonce of its signature if it is signed.
+    /// The provider pays for the storage until the patient decides on the record.
+    fn internal_submit_record(
+        &mut self,
+        patient_id: AccountId,
+        record: SubmittedRecord,
+        signature_nonce: Option<u64>,
+        method: &str,
+    ) -> u64 {
+        let provider_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        self.assert_provider_role(&provider_id, &AUTHOR_ROLES);
+        require!(
+            self.patients.contains_key(&patient_id),
+            "Patient not found."
+        );
+        let scope = record.scope();
+        require!(
+            self.granted_scopes(&patient_id, &provider_id)
+                .map(|scopes| scopes.contains(&scope))
+                .unwrap_or(false),
+            "Access not granted by patient."
+        );
+        if let Some(nonce) = signature_nonce {
+            self.internal_use_signature_nonce(&provider_id, record.id(), nonce);
+        }
+
+        let pending_id = self.next_pending_id;
+        self.next_pending_id += 1;
+
+        let mut pending = self.pending_records.get(&patient_id).unwrap_or_default();
+        pending.push(PendingRecord::new(
+            pending_id,
+            provider_id.clone(),
+            record,
+            env::block_timestamp(),
+        ));
+        self.pending_records.insert(&patient_id, &pending);
+        self.record_access(&patient_id, method, vec![scope]);
+        self.charge_storage(&provider_id, initial_storage);
+        log!(
+            "Provider with ID: {} submitted record {} for patient with ID: {}",
+            provider_id,
+            pending_id,
+            patient_id
+        );
+
+        pending_id
+    }
+
+    /// Remove a pending record from the patient's queue and return it
+    fn take_pending_record(&mut self, patient_id: &AccountId, pending_id: u64) -> PendingRecord {
+        let mut pending = self.pending_records.get(patient_id).unwrap_or_default();
+        let index = pending
+            .iter()
+            .position(|record| record.id() == pending_id)
+            .expect("Pending record not found.");
+        let record = pending.remove(index);
+        if pending.is_empty() {
+            self.pending_records.remove(patient_id);
+        } else {
+            self.pending_records.insert(patient_id, &pending);
+        }
+        record
+    }
+
+    /// Remove a pending record from the patient's queue and return it, releasing the
+    /// storage of the submission to the provider who paid for it. The signature nonce
+    /// and signing key of an accepted record are kept with it.
+    fn internal_discard_pending_record(
+        &mut self,
+        patient_id: &AccountId,
+        pending_id: u64,
+        accepted: bool,
+    ) -> PendingRecord {
+        let initial_storage = env::storage_usage();
+        let pending = self.take_pending_record(patient_id, pending_id);
+        if !accepted {
+            self.internal_forget_signature(pending.record().id());
+        }
+        self.charge_storage(&pending.author(), initial_storage);
+        pending
+    }
+
+    /// Discard every record pending for the patient, releasing their storage
+    /// to the providers who submitted them
+    pub(crate) fn internal_discard_pending_records(&mut self, patient_id: &AccountId) {
+        for pending in self.pending_records.get(patient_id).unwrap_or_default() {
+            self.internal_discard_pending_record(patient_id, pending.id(), false);
+        }
+    }
+}
+
+/// Check whether the pending record is past its expiry
+fn is_expired(pending: &PendingRecord) -> bool {
+    env::block_timestamp().saturating_sub(pending.submitted_at()) >= PENDING_RECORD_EXPIRY
+}
@@ -0,0 +1,130 @@
+use super::*;
+use crate::types::{Provider, ProviderRole};
+use near_sdk::{CurveType, PublicKey};
+
+#[near_bindgen]
+impl Contract {
+    /// Register the calling account as a healthcare provider.
+    /// The provider can't act in their role until an admin verifies them.
+    /// The attached deposit is added to the provider's storage balance.
+    #[payable]
+    pub fn register_provider(
+        &mut self,
+        full_name: String,
+        role: ProviderRole,
+        license_number: String,
+        organization: String,
+    ) {
+        let account_id = env::predecessor_account_id();
+        if env::attached_deposit() > 0 {
+            self.internal_storage_deposit(&account_id, env::attached_deposit());
+        }
+        let initial_storage = env::storage_usage();
+
+        require!(
+            !self.providers.contains_key(&account_id),
+            "Provider already exists."
+        );
+
+        let provider = Provider::new(full_name, role, license_number, organization);
+
+        self.providers.insert(&account_id, &provider);
+        self.charge_storage(&account_id, initial_storage);
+        log!("Registered provider successfully. ID: {}", account_id);
+    }
+
+    /// Register the ed25519 public key the calling provider signs records with
+    pub fn set_signing_key(&mut self, signing_key: PublicKey) {
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let mut provider = self.assert_provider(&account_id);
+        require!(
+            signing_key.curve_type() == CurveType::ED25519,
+            "Only ed25519 signing keys are supported."
+        );
+
+        provider.update_signing_key(signing_key);
+        self.providers.insert(&account_id, &provider);
+        self.charge_storage(&account_id, initial_storage);
+        log!("Updated signing key for provider with ID: {}", account_id);
+    }
+
+    /// Remove the calling account from the provider registry
+    pub fn deregister_provider(&mut self) {
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        self.assert_provider(&account_id);
+        log!("Removing provider with ID: {}", account_id);
+
+        self.providers.remove(&account_id);
+        self.verified_providers.remove(&account_id);
+        self.charge_storage(&account_id, initial_storage);
+
+        log!("Provider with ID: {} has been removed.", account_id);
+    }
+
+    /// Mark a registered provider as verified, after checking their license
+    /// and organization off-chain. Only callable by an admin.
+    pub fn verify_provider(&mut self, provider_id: AccountId) {
+        self.assert_admin();
+        self.assert_provider(&provider_id);
+        require!(
+            self.verified_providers.insert(&provider_id),
+            "Provider is already verified."
+        );
+        log!("Verified provider with ID: {}", provider_id);
+    }
+
+    /// Withdraw the verification of a provider. Only callable by an admin.
+    pub fn revoke_provider_verification(&mut self, provider_id: AccountId) {
+        self.assert_admin();
+        require!(
+            self.verified_providers.remove(&provider_id),
+            "Provider is not verified."
+        );
+        log!("Revoked verification of provider with ID: {}", provider_id);
+    }
+
+    /// Check whether the given account is a provider verified by an admin
+    pub fn is_verified_provider(&self, account_id: AccountId) -> bool {
+        self.verified_providers.contains(&account_id)
+    }
+
+    /// Get the provider profile of the given account
+    pub fn get_provider(&self, account_id: AccountId) -> Provider {
+        self.assert_provider(&account_id)
+    }
+
+    /// Check whether the given account is a registered provider,
+    /// optionally restricted to a specific role
+    pub fn is_provider(&self, account_id: AccountId, role: Option<ProviderRole>) -> bool {
+        match (self.providers.get(&account_id), role) {
+            (Some(provider), Some(role)) => provider.role() == role,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+impl Contract {
+    /// Return the provider profile of the given account,
+    /// panicking if the account is not a registered provider
+    pub fn assert_provider(&self, account_id: &AccountId) -> Provider {
+        self.providers.get(account_id).expect("Provider not found.")
+    }
+
+    /// Return the provider profile of the given account, panicking unless
+    /// the provider holds one of the given roles and has been verified by an admin
+    pub fn assert_provider_role(&self, account_id: &AccountId, roles: &[ProviderRole]) -> Provider {
+        let provider = self.assert_provider(account_id);
+        require!(
+            roles.contains(&provider.role()),
+            "Provider role is not authorized for this action."
+        );
+        require!(
+            self.verified_providers.contains(account_id),
+            "Provider is not verified."
+        );
+        provider
+    }
+}
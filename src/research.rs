@@ -0,0 +1,336 @@
+use super::*;
+use crate::types::{AgeGeneralization, GeneralizedAge, RecordKind};
+use std::ops::Bound;
+
+/// Maximum k, as a group of k staged records is released within a single call
+const MAX_K_ANONYMITY: u64 = 20;
+
+/// Secondary indices of the public records, each listing the positions
+/// of the matching records in `public_records` in the order they were published
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) enum PublicRecordIndex {
+    Kind(RecordKind),
+    /// Conditions are indexed case-insensitively, with whitespace collapsed
+    Condition(String),
+    Gender(Gender),
+}
+
+/// Attributes of a public record that could re-identify the patient when combined
+/// with other sources. A record is only released once at least k records share them.
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub(crate) struct QuasiIdentifier {
+    age: GeneralizedAge,
+    gender: Gender,
+    /// Normalized like the condition index. Treatments have no condition.
+    condition: Option<String>,
+}
+
+impl From<&PublicRecord> for QuasiIdentifier {
+    fn from(record: &PublicRecord) -> Self {
+        Self {
+            age: record.age(),
+            gender: record.gender(),
+            condition: record
+                .record_type()
+                .condition()
+                .map(|condition| normalize_condition(&condition)),
+        }
+    }
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Get a page of the public records of the given kind, oldest first
+    pub fn get_public_records_by_kind(
+        &self,
+        kind: RecordKind,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<PublicRecord> {
+        self.indexed_public_records(&PublicRecordIndex::Kind(kind), from_index, limit)
+    }
+
+    /// Get a page of the medical records and family medical records about
+    /// the given condition, oldest first. The condition is matched ignoring case.
+    pub fn get_public_records_by_condition(
+        &self,
+        condition: String,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<PublicRecord> {
+        let index = PublicRecordIndex::Condition(normalize_condition(&condition));
+        self.indexed_public_records(&index, from_index, limit)
+    }
+
+    /// Get a page of the public records of patients of the given gender, oldest first
+    pub fn get_public_records_by_gender(
+        &self,
+        gender: Gender,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<PublicRecord> {
+        self.indexed_public_records(&PublicRecordIndex::Gender(gender), from_index, limit)
+    }
+
+    /// Get a page of the medical records dated, and treatments started, between
+    /// the given dates (inclusive), in date order. `from_index` counts the records
+    /// within the range to skip.
+    pub fn get_public_records_by_date(
+        &self,
+        from_date: String,
+        to_date: String,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<PublicRecord> {
+        let from_date = parse_date("from_date", &from_date);
+        let to_date = parse_date("to_date", &to_date);
+        require!(from_date <= to_date, "Invalid date range.");
+        let limit = std::cmp::min(limit, MAX_PUBLIC_RECORDS_PAGE);
+
+        self.public_records_by_date
+            .range((
+                Bound::Included((from_date, 0)),
+                Bound::Included((to_date, u64::MAX)),
+            ))
+            .skip(usize::try_from(from_index).unwrap_or(usize::MAX))
+            .take(usize::try_from(limit).unwrap_or(usize::MAX))
+            .filter_map(|((_, index), _)| self.public_records.get(index))
+            .filter_map(|record| self.current_public_record(record))
+            .collect()
+    }
+
+    /// Set how the age of patients is generalized in the records published from now on.
+    /// Only callable by an admin.
+    pub fn set_age_generalization(&mut self, generalization: AgeGeneralization) {
+        self.assert_admin();
+        require!(
+            generalization != AgeGeneralization::AgeBand { years: 0 },
+            "Age bands must span at least one year."
+        );
+        self.age_generalization = generalization;
+        log!("Set age generalization to {:?}", generalization);
+    }
+
+    /// Get how the age of patients is generalized in public records
+    pub fn get_age_generalization(&self) -> AgeGeneralization {
+        self.age_generalization
+    }
+
+    /// Set the minimum number of public records that must share an age, gender and
+    /// condition before they are released. Only callable by the owner.
+    pub fn set_k_anonymity(&mut self, k: u64) {
+        self.assert_owner();
+        require!(
+            (1..=MAX_K_ANONYMITY).contains(&k),
+            format!("k must be between 1 and {}.", MAX_K_ANONYMITY)
+        );
+        self.k_anonymity = k;
+        log!("Set k-anonymity to {}", k);
+    }
+
+    /// Get the minimum number of public records that must share an age, gender and
+    /// condition before they are released
+    pub fn get_k_anonymity(&self) -> u64 {
+        self.k_anonymity
+    }
+
+    /// Get the number of public records staged until enough records share
+    /// their age, gender and condition
+    pub fn staged_public_records_count(&self) -> u64 {
+        self.total_staged_public_records
+    }
+
+    /// Release the staged records of a page of the staged groups that reached k records,
+    /// e.g. after k was lowered. Released groups leave the staging area, so the remaining
+    /// groups are paged from 0 again. At most `MAX_PUBLIC_RECORDS_PAGE` groups are checked.
+    /// Returns the number of records released. Only callable by the owner.
+    pub fn release_staged_public_records(&mut self, from_index: u64, limit: u64) -> u64 {
+        self.assert_owner();
+        let limit = std::cmp::min(limit, MAX_PUBLIC_RECORDS_PAGE);
+        let groups: Vec<QuasiIdentifier> = self
+            .staged_public_records
+            .keys()
+            .skip(usize::try_from(from_index).unwrap_or(usize::MAX))
+            .take(usize::try_from(limit).unwrap_or(usize::MAX))
+            .collect();
+        let mut released = 0;
+
+        for group in groups {
+            let staged = self
+                .staged_public_records
+                .get(&group)
+                .expect("Staged public records not found.");
+            if self.public_record_group_size(&group) + staged.len() >= self.k_anonymity {
+                released += self.internal_release_group(&group, staged);
+            }
+        }
+
+        log!("Released {} staged public records", released);
+        released
+    }
+
+    /// Replace the exact birthday held by a page of the public records published
+    /// before ages were generalized with the generalized age.
+    /// Returns the number of records rewritten. Only callable by an admin.
+    pub fn anonymize_public_records(&mut self, from_index: u64, limit: u64) -> u64 {
+        self.assert_admin();
+        let to_index = std::cmp::min(from_index.saturating_add(limit), self.public_records.len());
+        let mut anonymized = 0;
+
+        for index in from_index..to_index {
+            let record = self
+                .public_records
+                .get(index)
+                .expect("Public record not found.");
+            if record.is_generalized() {
+                continue;
+            }
+            let record = self
+                .current_public_record(record)
+                .expect("Public record not found.");
+            self.public_records.replace(index, &record.into());
+            anonymized += 1;
+        }
+
+        log!("Anonymized {} public records", anonymized);
+        anonymized
+    }
+}
+
+impl Contract {
+    /// Stage the record with the others sharing its quasi-identifiers, releasing
+    /// them all once there are k of them. Records joining a released group
+    /// are released immediately.
+    pub(crate) fn internal_stage_public_record(&mut self, record: PublicRecord) {
+        let group = QuasiIdentifier::from(&record);
+        let mut staged = self.staged_public_records.get(&group).unwrap_or_else(|| {
+            Vector::new(
+                StorageKey::StagedPublicRecords {
+                    group_hash: env::sha256(&group.try_to_vec().unwrap()),
+                }
+                .try_to_vec()
+                .unwrap(),
+            )
+        });
+        staged.push(&record);
+        self.total_staged_public_records += 1;
+
+        if self.public_record_group_size(&group) + staged.len() >= self.k_anonymity {
+            self.internal_release_group(&group, staged);
+        } else {
+            if let Some(contributor) = record.contributor() {
+                self.public_record_commitments.insert(
+                    &contributor.into(),
+                    &PublicRecordLocation::Staged(group.clone()),
+                );
+            }
+            self.staged_public_records.insert(&group, &staged);
+            log!(
+                "Staged public record until {} records share its age, gender and condition",
+                self.k_anonymity
+            );
+        }
+    }
+
+    /// Release the staged records of the group into the public records,
+    /// and empty its staging area. Returns the number of records released.
+    fn internal_release_group(
+        &mut self,
+        group: &QuasiIdentifier,
+        mut staged: Vector<PublicRecord>,
+    ) -> u64 {
+        let released = staged.len();
+        for record in staged.iter() {
+            self.internal_release_public_record(record);
+        }
+        staged.clear();
+        self.staged_public_records.remove(group);
+        self.total_staged_public_records -= released;
+        self.public_record_group_sizes
+            .insert(group, &(self.public_record_group_size(group) + released));
+        released
+    }
+
+    /// Get the number of released public records sharing the given quasi-identifiers
+    fn public_record_group_size(&self, group: &QuasiIdentifier) -> u64 {
+        self.public_record_group_sizes.get(group).unwrap_or(0)
+    }
+
+    /// Add the public record at the given position to the secondary indices
+    pub(crate) fn index_public_record(&mut self, index: u64, record: &PublicRecord) {
+        let record_type = record.record_type();
+        let mut keys = vec![
+            PublicRecordIndex::Kind(record_type.kind()),
+            PublicRecordIndex::Gender(record.gender()),
+        ];
+        if let Some(condition) = record_type.condition() {
+            keys.push(PublicRecordIndex::Condition(normalize_condition(
+                &condition,
+            )));
+        }
+        for key in keys {
+            let mut positions = self.public_record_indices.get(&key).unwrap_or_else(|| {
+                Vector::new(
+                    StorageKey::PublicRecordIndex {
+                        index_hash: env::sha256(&key.try_to_vec().unwrap()),
+                    }
+                    .try_to_vec()
+                    .unwrap(),
+                )
+            });
+            positions.push(&index);
+            self.public_record_indices.insert(&key, &positions);
+        }
+
+        if let Some(date) = record_type.date() {
+            self.public_records_by_date.insert(&(date, index), &());
+        }
+        self.count_public_record(record, true);
+    }
+
+    /// Remove the withdrawn public record at the given position from the date index,
+    /// the aggregate counts and the size of its group. The other indices skip withdrawn records when read.
+    pub(crate) fn unindex_public_record(&mut self, index: u64, record: &PublicRecord) {
+        if let Some(date) = record.record_type().date() {
+            self.public_records_by_date.remove(&(date, index));
+        }
+        self.count_public_record(record, false);
+        let group = QuasiIdentifier::from(record);
+        let size = self.public_record_group_size(&group);
+        self.public_record_group_sizes
+            .insert(&group, &size.saturating_sub(1));
+    }
+
+    /// Read a page of the public records listed by the given index
+    fn indexed_public_records(
+        &self,
+        key: &PublicRecordIndex,
+        from_index: u64,
+        limit: u64,
+    ) -> Vec<PublicRecord> {
+        let positions = match self.public_record_indices.get(key) {
+            Some(positions) => positions,
+            None => return vec![],
+        };
+        let to_index = std::cmp::min(
+            from_index.saturating_add(std::cmp::min(limit, MAX_PUBLIC_RECORDS_PAGE)),
+            positions.len(),
+        );
+
+        (from_index..to_index)
+            .filter_map(|position| positions.get(position))
+            .filter_map(|index| self.public_records.get(index))
+            .filter_map(|record| self.current_public_record(record))
+            .collect()
+    }
+}
+
+/// Normalize a condition for indexing, so that spellings differing only
+/// in case or whitespace match
+pub(crate) fn normalize_condition(condition: &str) -> String {
+    condition
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
@@ -0,0 +1,83 @@
+use super::*;
+use crate::types::Tombstone;
+
+#[near_bindgen]
+impl Contract {
+    /// Retract one of the calling account's entries by ID, removing it from
+    /// their information while keeping a tombstone with the given reason
+    pub fn retract_record(&mut self, record_id: u64, reason: String) {
+        let account_id = env::predecessor_account_id();
+        self.internal_retract_record(account_id, record_id, reason, "retract_record");
+    }
+
+    /// Retract an entry of a patient the calling account is guardian of
+    pub fn retract_record_for(&mut self, patient_id: AccountId, record_id: u64, reason: String) {
+        self.assert_guardian(&patient_id);
+        self.internal_retract_record(patient_id, record_id, reason, "retract_record_for");
+    }
+
+    /// Get the tombstones of the entries the calling account retracted.
+    /// This is a change method, as view calls can't identify the caller.
+    pub fn get_retracted_records(&mut self) -> Vec<Tombstone> {
+        let account_id = env::predecessor_account_id();
+        require!(
+            self.patients.contains_key(&account_id),
+            "Patient not found."
+        );
+
+        self.retracted_records.get(&account_id).unwrap_or_default()
+    }
+}
+
+impl Contract {
+    /// Remove an entry of the given patient, replacing it with a tombstone
+    fn internal_retract_record(
+        &mut self,
+        account_id: AccountId,
+        record_id: u64,
+        reason: String,
+        method: &str,
+    ) {
+        let initial_storage = env::storage_usage();
+        require!(
+            !reason.trim().is_empty(),
+            "A retraction reason is required."
+        );
+        let mut records = self.internal_patient_records(&account_id);
+        log!(
+            "Retracting record {} for patient with ID: {}",
+            record_id,
+            account_id
+        );
+        log!("Reason: {}", reason);
+
+        let record = records.remove_entry(record_id).expect("Record not found.");
+        let scope = record.scope();
+
+        // Keep the retracted entry for auditing
+        let mut tombstones = self.retracted_records.get(&account_id).unwrap_or_default();
+        tombstones.push(Tombstone::new(
+            record,
+            env::block_timestamp(),
+            env::predecessor_account_id(),
+            reason,
+        ));
+
+        // Update the patient's collections and tombstones in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.retracted_records.insert(&account_id, &tombstones);
+        self.record_access(&account_id, method, vec![scope]);
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordRetracted {
+            patient_id: &account_id,
+            record_id,
+            section: scope,
+        }
+        .emit();
+        log!(
+            "Retracted record {} for patient with ID: {}",
+            record_id,
+            account_id
+        );
+    }
+}
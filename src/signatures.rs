@@ -0,0 +1,196 @@
+use super::*;
+use ed25519_dalek::Verifier;
+use near_sdk::json_types::Base64VecU8;
+
+#[near_bindgen]
+impl Contract {
+    /// Add a medical record signed by the provider who issued it to the calling account.
+    /// The signature covers the Borsh encoding of the calling account's ID, the signature
+    /// nonce and the record with `author` set and no signature or ID, and is verified
+    /// against the provider's signing key. Each nonce can only be used once per provider.
+    /// Returns the ID assigned to the record.
+    pub fn add_signed_medical_record(
+        &mut self,
+        condition: String,
+        record_data: String,
+        date: String,
+        author: AccountId,
+        signature: Base64VecU8,
+        signature_nonce: u64,
+    ) -> u64 {
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let mut records = self.internal_patient_records(&account_id);
+        log!(
+            "Adding signed medical record for patient with ID: {}",
+            account_id
+        );
+        log!("Author: {}", author);
+
+        let id = self.assign_record_id();
+        let medical_record =
+            MedicalRecord::new(id, condition, record_data, parse_date("date", &date))
+                .with_author(author.clone())
+                .with_signature(signature.into());
+        self.assert_record_signature(
+            &author,
+            &medical_record.signing_payload(&account_id, signature_nonce),
+            medical_record.signature(),
+        );
+        self.internal_use_signature_nonce(&author, id, signature_nonce);
+
+        // Add the medical record to the patient's medical records
+        records.add_medical_record(medical_record);
+
+        // Update the patient's collections in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_access(
+            &account_id,
+            "add_signed_medical_record",
+            vec![AccessScope::MedicalRecords],
+        );
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAdded {
+            patient_id: &account_id,
+            record_id: id,
+            section: AccessScope::MedicalRecords,
+        }
+        .emit();
+        log!(
+            "Added signed medical record for patient with ID: {}",
+            account_id
+        );
+        id
+    }
+
+    /// Add a treatment signed by the provider who issued it to the calling account.
+    /// The signature covers the Borsh encoding of the calling account's ID, the signature
+    /// nonce and the treatment with `author` set and no signature or ID, and is verified
+    /// against the provider's signing key. Each nonce can only be used once per provider.
+    /// Returns the ID assigned to the treatment.
+    pub fn add_signed_treatment(
+        &mut self,
+        treatment: String,
+        dosage: String,
+        start_date: String,
+        finish_date: String,
+        reason: Option<String>,
+        author: AccountId,
+        signature: Base64VecU8,
+        signature_nonce: u64,
+    ) -> u64 {
+        let account_id = env::predecessor_account_id();
+        let initial_storage = env::storage_usage();
+        let mut records = self.internal_patient_records(&account_id);
+        log!(
+            "Adding signed treatment for patient with ID: {}",
+            account_id
+        );
+        log!("Author: {}", author);
+
+        let (start_date, finish_date) = parse_treatment_period(&start_date, &finish_date);
+        let id = self.assign_record_id();
+        let treatment = Treatment::new(id, treatment, dosage, start_date, finish_date, reason)
+            .with_author(author.clone())
+            .with_signature(signature.into());
+        self.assert_record_signature(
+            &author,
+            &treatment.signing_payload(&account_id, signature_nonce),
+            treatment.signature(),
+        );
+        self.internal_use_signature_nonce(&author, id, signature_nonce);
+
+        // Add the treatment to the patient's treatments
+        records.add_treatment(treatment);
+
+        // Update the patient's collections in the contract storage
+        self.patient_records.insert(&account_id, &records);
+        self.record_access(
+            &account_id,
+            "add_signed_treatment",
+            vec![AccessScope::Treatments],
+        );
+        self.charge_storage(&account_id, initial_storage);
+        Event::RecordAdded {
+            patient_id: &account_id,
+            record_id: id,
+            section: AccessScope::Treatments,
+        }
+        .emit();
+        log!("Added signed treatment for patient with ID: {}", account_id);
+        id
+    }
+
+    /// Get the nonce the provider signed the record with the given ID with,
+    /// needed to re-verify its signature. Returns None for unsigned records.
+    pub fn get_signature_nonce(&self, record_id: u64) -> Option<u64> {
+        self.signature_nonces.get(&record_id)
+    }
+
+    /// Get the signing key the signature of the record with the given ID was verified
+    /// against, which stays valid after the provider rotates their key.
+    /// Returns None for unsigned records.
+    pub fn get_record_signing_key(&self, record_id: u64) -> Option<PublicKey> {
+        self.record_signing_keys.get(&record_id)
+    }
+}
+
+impl Contract {
+    /// Panic unless the signature is a valid ed25519 signature of the payload
+    /// by the signing key registered for the given provider, who must be verified
+    pub fn assert_record_signature(
+        &self,
+        provider_id: &AccountId,
+        payload: &[u8],
+        signature: Option<Vec<u8>>,
+    ) {
+        let provider = self.assert_provider(provider_id);
+        require!(
+            self.verified_providers.contains(provider_id),
+            "Provider is not verified."
+        );
+        let signing_key = provider
+            .signing_key()
+            .expect("Provider has no signing key.");
+        // Skip the curve type prefix of the NEAR public key
+        let public_key = ed25519_dalek::PublicKey::from_bytes(&signing_key.as_bytes()[1..])
+            .expect("Invalid provider signing key.");
+        let signature = signature
+            .and_then(|signature| ed25519_dalek::Signature::from_bytes(&signature).ok())
+            .expect("Invalid record signature.");
+
+        require!(
+            public_key.verify(payload, &signature).is_ok(),
+            "Invalid record signature."
+        );
+    }
+
+    /// Mark the provider's signature nonce as used, panicking if it already was,
+    /// so that a signed record can't be added more than once, and keep it with the
+    /// ID of the record it signs, along with the provider's current signing key
+    pub(crate) fn internal_use_signature_nonce(
+        &mut self,
+        provider_id: &AccountId,
+        record_id: u64,
+        nonce: u64,
+    ) {
+        require!(
+            self.used_signature_nonces
+                .insert(&(provider_id.clone(), nonce)),
+            "Signature nonce already used."
+        );
+        self.signature_nonces.insert(&record_id, &nonce);
+        let signing_key = self
+            .assert_provider(provider_id)
+            .signing_key()
+            .expect("Provider has no signing key.");
+        self.record_signing_keys.insert(&record_id, &signing_key);
+    }
+
+    /// Forget the signature nonce and signing key kept for the given record,
+    /// once its signature no longer applies
+    pub(crate) fn internal_forget_signature(&mut self, record_id: u64) {
+        self.signature_nonces.remove(&record_id);
+        self.record_signing_keys.remove(&record_id);
+    }
+}
@@ -0,0 +1,147 @@
+use super::*;
+use crate::research::normalize_condition;
+use crate::types::{DemographicCount, RecordKind};
+
+/// Aggregate counters of the public records, kept up to date as records
+/// are released and withdrawn
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) enum PublicRecordCounter {
+    Kind(RecordKind),
+    /// Conditions are counted case-insensitively, with whitespace collapsed
+    Condition(String),
+    /// Treatment names are counted like conditions
+    Treatment(String),
+    Gender(Gender),
+    /// Conditions of the patients' relatives, from family medical records
+    FamilyCondition(String),
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Get the number of public records of the given kind
+    pub fn count_by_kind(&self, kind: RecordKind) -> u64 {
+        self.public_record_count(&PublicRecordCounter::Kind(kind))
+    }
+
+    /// Get the number of medical records about the given condition.
+    /// The condition is matched ignoring case.
+    pub fn count_by_condition(&self, condition: String) -> u64 {
+        self.public_record_count(&PublicRecordCounter::Condition(normalize_condition(
+            &condition,
+        )))
+    }
+
+    /// Get the number of family medical records about the given condition,
+    /// which a relative of the patient has. The condition is matched ignoring case.
+    pub fn count_by_family_condition(&self, condition: String) -> u64 {
+        self.public_record_count(&PublicRecordCounter::FamilyCondition(normalize_condition(
+            &condition,
+        )))
+    }
+
+    /// Get the number of public treatments with the given name.
+    /// The name is matched ignoring case.
+    pub fn count_by_treatment(&self, treatment: String) -> u64 {
+        self.public_record_count(&PublicRecordCounter::Treatment(normalize_condition(
+            &treatment,
+        )))
+    }
+
+    /// Get the number of public records of patients of the given gender
+    pub fn count_by_gender(&self, gender: Gender) -> u64 {
+        self.public_record_count(&PublicRecordCounter::Gender(gender))
+    }
+
+    /// Get the number of medical records about the given condition for each gender
+    /// and generalized age, in the order they first appeared. Family medical records
+    /// are left out, as the condition isn't the patient's. Combinations without records are omitted.
+    pub fn get_condition_distribution(&self, condition: String) -> Vec<DemographicCount> {
+        self.condition_distributions
+            .get(&normalize_condition(&condition))
+            .map(|distribution| {
+                distribution
+                    .iter()
+                    .map(|((gender, age), count)| DemographicCount::new(gender, age, count))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl Contract {
+    /// Add the public record to the aggregate counters, or remove it when withdrawn
+    pub(crate) fn count_public_record(&mut self, record: &PublicRecord, added: bool) {
+        let record_type = record.record_type();
+        let mut counters = vec![
+            PublicRecordCounter::Kind(record_type.kind()),
+            PublicRecordCounter::Gender(record.gender()),
+        ];
+        if let Some(treatment) = record_type.treatment() {
+            counters.push(PublicRecordCounter::Treatment(normalize_condition(
+                &treatment,
+            )));
+        }
+        let condition = record_type
+            .condition()
+            .map(|condition| normalize_condition(&condition));
+        // The condition of a family medical record is a relative's, so it is counted
+        // apart and left out of the distribution by the patient's gender and age
+        let (condition, family_condition) = match record_type.kind() {
+            RecordKind::FamilyMedicalRecord => (None, condition),
+            _ => (condition, None),
+        };
+        if let Some(condition) = &condition {
+            counters.push(PublicRecordCounter::Condition(condition.clone()));
+        }
+        if let Some(condition) = family_condition {
+            counters.push(PublicRecordCounter::FamilyCondition(condition));
+        }
+
+        for counter in counters {
+            let count = update_count(self.public_record_count(&counter), added);
+            if count == 0 {
+                self.public_record_counts.remove(&counter);
+            } else {
+                self.public_record_counts.insert(&counter, &count);
+            }
+        }
+
+        if let Some(condition) = condition {
+            let mut distribution =
+                self.condition_distributions
+                    .get(&condition)
+                    .unwrap_or_else(|| {
+                        UnorderedMap::new(
+                            StorageKey::ConditionDistribution {
+                                condition_hash: env::sha256(condition.as_bytes()),
+                            }
+                            .try_to_vec()
+                            .unwrap(),
+                        )
+                    });
+            let cell = (record.gender(), record.age());
+            let count = update_count(distribution.get(&cell).unwrap_or(0), added);
+            if count == 0 {
+                distribution.remove(&cell);
+            } else {
+                distribution.insert(&cell, &count);
+            }
+            self.condition_distributions
+                .insert(&condition, &distribution);
+        }
+    }
+
+    /// Get the value of the given aggregate counter
+    fn public_record_count(&self, counter: &PublicRecordCounter) -> u64 {
+        self.public_record_counts.get(counter).unwrap_or(0)
+    }
+}
+
+/// Increment a count for an added record, or decrement it for a withdrawn one
+fn update_count(count: u64, added: bool) -> u64 {
+    if added {
+        count + 1
+    } else {
+        count.saturating_sub(1)
+    }
+}
@@ -0,0 +1,182 @@
+use super::*;
+use crate::types::{StorageAccount, StorageBalance, StorageBalanceBounds};
+use near_sdk::{assert_one_yocto, json_types::U128, Balance, StorageUsage};
+
+/// Storage (in bytes) an account must be able to pay for when registering: its storage
+/// account, a patient's demographics and collections, and the first audit log entry
+const MIN_STORAGE_BYTES: StorageUsage = 2_000;
+
+#[near_bindgen]
+impl Contract {
+    /// Deposit the attached NEAR to pay for the storage of the given account,
+    /// or of the calling account. With `registration_only`, only the minimum
+    /// balance is kept when registering and the rest of the deposit is refunded.
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+
+        let refund = if self.storage_accounts.contains_key(&account_id) {
+            if registration_only.unwrap_or(false) {
+                amount
+            } else {
+                self.internal_storage_deposit(&account_id, amount);
+                0
+            }
+        } else {
+            let min = self.storage_balance_bounds().min().0;
+            require!(
+                amount >= min,
+                "The attached deposit is less than the minimum storage balance."
+            );
+            let deposit = if registration_only.unwrap_or(false) {
+                min
+            } else {
+                amount
+            };
+            self.internal_storage_deposit(&account_id, deposit);
+            amount - deposit
+        };
+        if refund > 0 {
+            Promise::new(env::predecessor_account_id()).transfer(refund);
+        }
+
+        self.storage_balance_of(account_id)
+            .expect("Account is not registered.")
+    }
+
+    /// Withdraw the given amount, or all of the available balance, from the
+    /// calling account's storage deposit. Requires exactly 1 yoctoNEAR attached.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let account = self
+            .storage_accounts
+            .get(&account_id)
+            .expect("Account is not registered.");
+        let amount = amount.map(|amount| amount.0).unwrap_or(account.available());
+        require!(
+            amount <= account.available(),
+            "The amount is greater than the available storage balance."
+        );
+
+        self.internal_storage_refund(&account_id, amount);
+        self.storage_balance_of(account_id)
+            .expect("Account is not registered.")
+    }
+
+    /// Unregister the calling account, refunding its storage deposit except for
+    /// the storage still used by its audit log. A patient must be removed first,
+    /// or `force` must be set to remove them along with the registration.
+    /// Requires exactly 1 yoctoNEAR attached. Returns false if the account was not registered.
+    #[payable]
+    pub fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        if !self.storage_accounts.contains_key(&account_id) {
+            return false;
+        }
+        if self.patients.contains_key(&account_id) {
+            require!(
+                force.unwrap_or(false),
+                "Cannot unregister a patient. Remove the patient first or use force."
+            );
+            self.internal_discard_pending_records(&account_id);
+            let initial_storage = env::storage_usage();
+            self.internal_remove_patient(&account_id);
+            self.charge_storage(&account_id, initial_storage);
+        }
+
+        let initial_storage = env::storage_usage();
+        let mut account = self
+            .storage_accounts
+            .remove(&account_id)
+            .expect("Account is not registered.");
+        account.free_bytes(initial_storage - env::storage_usage());
+        if account.available() > 0 {
+            Promise::new(account_id.clone()).transfer(account.available());
+        }
+        log!("Unregistered storage for account with ID: {}", account_id);
+        true
+    }
+
+    /// Get the minimum storage balance required to register an account.
+    /// There is no maximum, as patients pay for every byte their information uses.
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds::new(
+            U128(Balance::from(MIN_STORAGE_BYTES) * env::storage_byte_cost()),
+            None,
+        )
+    }
+
+    /// Get the storage balance of the given account, if registered
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_accounts
+            .get(&account_id)
+            .map(|account| StorageBalance::from(&account))
+    }
+}
+
+impl Contract {
+    /// Add the amount to the storage deposit of the given account,
+    /// registering the account and charging it for its registration if needed
+    pub(crate) fn internal_storage_deposit(&mut self, account_id: &AccountId, amount: Balance) {
+        match self.storage_accounts.get(account_id) {
+            Some(mut account) => {
+                account.deposit(amount);
+                self.storage_accounts.insert(account_id, &account);
+            }
+            None => {
+                let initial_storage = env::storage_usage();
+                self.storage_accounts
+                    .insert(account_id, &StorageAccount::new(amount));
+                self.charge_storage(account_id, initial_storage);
+                log!("Registered storage for account with ID: {}", account_id);
+            }
+        }
+    }
+
+    /// Charge the given account for the storage used since `initial_storage`, or
+    /// release the storage freed since then. Panics if the account's deposit
+    /// doesn't cover its storage. Storage freed by unregistered accounts, such
+    /// as patients migrated from a previous version, is kept by the contract.
+    pub(crate) fn charge_storage(&mut self, account_id: &AccountId, initial_storage: StorageUsage) {
+        let current_storage = env::storage_usage();
+        let account = self.storage_accounts.get(account_id);
+        if account.is_none() && current_storage <= initial_storage {
+            return;
+        }
+        let mut account =
+            account.expect("Account is not registered for storage. Call storage_deposit first.");
+
+        if current_storage > initial_storage {
+            account.use_bytes(current_storage - initial_storage);
+        } else {
+            account.free_bytes(initial_storage - current_storage);
+        }
+        require!(
+            account.locked() <= account.total(),
+            "Insufficient storage deposit. Call storage_deposit to add more."
+        );
+        self.storage_accounts.insert(account_id, &account);
+    }
+
+    /// Transfer the amount from the storage deposit of the given account back to it
+    pub(crate) fn internal_storage_refund(&mut self, account_id: &AccountId, amount: Balance) {
+        if amount == 0 {
+            return;
+        }
+        let mut account = self
+            .storage_accounts
+            .get(account_id)
+            .expect("Account is not registered.");
+        account.withdraw(amount);
+        self.storage_accounts.insert(account_id, &account);
+        Promise::new(account_id.clone()).transfer(amount);
+    }
+}
@@ -1,8 +1,23 @@
 use super::*;
+use crate::legacy::{
+    ContractV2, LegacyFamilyMedicalRecord, LegacyImmunization, LegacyPatient, LegacyPublicRecord,
+    LegacyRecordType,
+};
+use crate::types::{
+    AccessScope, AgeGeneralization, AllergySeverity, AmendableRecord, BloodType, Date, Gender,
+    GeneralizedAge, GrantStatus, ProviderRole, PublicRecordV1, RecordKind,
+};
+use ed25519_dalek::{Keypair, PublicKey as Ed25519PublicKey, SecretKey, Signer};
 use near_sdk::{
-    test_utils::{accounts, VMContextBuilder},
-    testing_env,
+    json_types::Base64VecU8,
+    mock::VmAction,
+    test_utils::{accounts, get_created_receipts, get_logs, VMContextBuilder},
+    testing_env, Balance, PublicKey,
 };
+use std::collections::HashMap;
+
+// Storage deposit attached to the calls made in the tests (1 NEAR)
+const STORAGE_DEPOSIT: Balance = 1_000_000_000_000_000_000_000_000;
 
 // Allows for modifying the environment of the mocked blockchain
 fn get_context(predecessor_account_id: AccountId) -> VMContextBuilder {
@@ -12,10 +27,43 @@ fn get_context(predecessor_account_id: AccountId) -> VMContextBuilder {
     builder
         .current_account_id(accounts(0))
         .signer_account_id(predecessor_account_id.clone())
-        .predecessor_account_id(predecessor_account_id);
+        .predecessor_account_id(predecessor_account_id)
+        .attached_deposit(STORAGE_DEPOSIT);
     builder
 }
 
+// Commit to a contribution to the public records with the given secret,
+// as the patient does off-chain before publishing a record
+fn get_commitment(patient_id: &AccountId, secret: &str) -> Base64VecU8 {
+    Base64VecU8(env::sha256(
+        &[patient_id.as_bytes(), secret.as_bytes()].concat(),
+    ))
+}
+
+// Deterministic ed25519 key pair used to sign records in the tests
+fn get_signing_keypair() -> Keypair {
+    let secret = SecretKey::from_bytes(&[7u8; 32]).unwrap();
+    let public = Ed25519PublicKey::from(&secret);
+    Keypair { secret, public }
+}
+
+// Convert an ed25519 public key into a NEAR public key
+fn to_near_public_key(public_key: &Ed25519PublicKey) -> PublicKey {
+    // Prefix the key with the ed25519 curve type
+    let mut bytes = vec![0u8];
+    bytes.extend_from_slice(public_key.as_bytes());
+    PublicKey::try_from(bytes).unwrap()
+}
+
+// Returns the part of the account's storage balance that is not locked for storage
+fn get_available_balance(contract: &Contract, account_id: AccountId) -> Balance {
+    contract
+        .storage_balance_of(account_id)
+        .unwrap()
+        .available()
+        .0
+}
+
 #[test]
 fn test_patient_creation_success() {
     // Arrange
@@ -33,9 +81,9 @@ fn test_patient_creation_success() {
     // Call the add_patient method to create the new patient.
     contract.add_patient(
         "Jack Johnson".to_string(),
-        "01/02/2023".to_string(),
-        "Male".to_string(),
-        "A+".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
     );
 
     // Assert
@@ -47,9 +95,9 @@ fn test_patient_creation_success() {
 
     // Assert that the patient was created successfully.
     assert_eq!(patient.full_name(), "Jack Johnson".to_string());
-    assert_eq!(patient.birthday(), "01/02/2023".to_string());
-    assert_eq!(patient.gender(), "Male".to_string());
-    assert_eq!(patient.blood_type(), "A+".to_string());
+    assert_eq!(patient.birthday().to_string(), "2023-01-02".to_string());
+    assert_eq!(patient.gender(), Gender::Male);
+    assert_eq!(patient.blood_type(), BloodType::APositive);
 
     // Assert that the patient has no records, allergies, immunizations, or procedures
     assert!(patient.records().is_empty());
@@ -76,18 +124,18 @@ fn test_patient_creation_duplicate() {
     // Call the add_patient method to create the new patient.
     contract.add_patient(
         "Jack Johnson".to_string(),
-        "01/02/2023".to_string(),
-        "Male".to_string(),
-        "A+".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
     );
 
     // Assert
     // Try to add the same patient again, which should fail.
     contract.add_patient(
         "Jack Johnson".to_string(),
-        "01/02/2023".to_string(),
-        "Male".to_string(),
-        "A+".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
     );
 }
 
@@ -124,16 +172,16 @@ fn test_update_patient_details() {
     // Call the add_patient method to create the new patient.
     contract.add_patient(
         "John Doe".to_string(),
-        "01/01/1990".to_string(),
-        "Male".to_string(),
-        "O+".to_string(),
+        "1990-01-01".to_string(),
+        Gender::Male,
+        BloodType::OPositive,
     );
 
     // Update patient details
     contract.full_name("Jane Smith".to_string());
-    contract.birthday("02/15/1985".to_string());
-    contract.gender("Female".to_string());
-    contract.blood_type("A-".to_string());
+    contract.birthday("1985-02-15".to_string());
+    contract.gender(Gender::Female);
+    contract.blood_type(BloodType::ANegative);
 
     // Assert
     // Call get_patient method to get the updated patient information
@@ -141,9 +189,9 @@ fn test_update_patient_details() {
 
     // Assert that the patient details have been updated successfully.
     assert_eq!(patient.full_name(), "Jane Smith".to_string());
-    assert_eq!(patient.birthday(), "02/15/1985".to_string());
-    assert_eq!(patient.gender(), "Female".to_string());
-    assert_eq!(patient.blood_type(), "A-".to_string());
+    assert_eq!(patient.birthday().to_string(), "1985-02-15".to_string());
+    assert_eq!(patient.gender(), Gender::Female);
+    assert_eq!(patient.blood_type(), BloodType::ANegative);
 }
 
 #[test]
@@ -163,26 +211,26 @@ fn test_patient_allergy_immunization_procedure() {
     // Call the add_patient method to create the new patient.
     contract.add_patient(
         "Jack Johnson".to_string(),
-        "01/02/2023".to_string(),
-        "Male".to_string(),
-        "A+".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
     );
 
     // Add a new allergy to the patient's allergies
-    contract.add_allergy("Peanuts".to_string(), "Mild".to_string());
+    contract.add_allergy("Peanuts".to_string(), AllergySeverity::Mild);
 
     // Add a new immunization to the patient's immunizations
-    contract.add_immunization("Flu Shot".to_string(), "01/01/2021".to_string());
+    contract.add_immunization("Flu Shot".to_string(), "2021-01-01".to_string());
 
     // Add a new procedure to the patient's procedures
     contract.add_procedure(
         "Appendectomy".to_string(),
-        "01/01/2021".to_string(),
+        "2021-01-01".to_string(),
         "N/A".to_string(),
     );
 
     // Add another allergy to the patient's allergies
-    contract.add_allergy("Shellfish".to_string(), "Severe".to_string());
+    contract.add_allergy("Shellfish".to_string(), AllergySeverity::Severe);
 
     // Assert
     // Assert that the patient was created successfully.
@@ -193,9 +241,9 @@ fn test_patient_allergy_immunization_procedure() {
 
     // Assert that the patient was created successfully.
     assert_eq!(patient.full_name(), "Jack Johnson".to_string());
-    assert_eq!(patient.birthday(), "01/02/2023".to_string());
-    assert_eq!(patient.gender(), "Male".to_string());
-    assert_eq!(patient.blood_type(), "A+".to_string());
+    assert_eq!(patient.birthday().to_string(), "2023-01-02".to_string());
+    assert_eq!(patient.gender(), Gender::Male);
+    assert_eq!(patient.blood_type(), BloodType::APositive);
 
     // Assert that the patient has the correct number of records, allergies, immunizations, and procedures
     assert!(patient.records().is_empty());
@@ -205,12 +253,2808 @@ fn test_patient_allergy_immunization_procedure() {
 
     // Assert that the patient has the correct allergies, immunizations, and procedures
     assert_eq!(patient.allergies()[0].allergen(), "Peanuts".to_string());
-    assert_eq!(patient.allergies()[0].severity(), "Mild".to_string());
+    assert_eq!(patient.allergies()[0].severity(), AllergySeverity::Mild);
     assert_eq!(patient.allergies()[1].allergen(), "Shellfish".to_string());
-    assert_eq!(patient.allergies()[1].severity(), "Severe".to_string());
+    assert_eq!(patient.allergies()[1].severity(), AllergySeverity::Severe);
     assert_eq!(patient.immunizations()[0].name(), "Flu Shot".to_string());
-    assert_eq!(patient.immunizations()[0].date(), "01/01/2021".to_string());
+    assert_eq!(
+        patient.immunizations()[0].date().to_string(),
+        "2021-01-01".to_string()
+    );
     assert_eq!(patient.procedures()[0].name(), "Appendectomy".to_string());
-    assert_eq!(patient.procedures()[0].date(), "01/01/2021".to_string());
+    assert_eq!(
+        patient.procedures()[0].date().to_string(),
+        "2021-01-01".to_string()
+    );
     assert_eq!(patient.procedures()[0].description(), "N/A".to_string());
 }
+
+#[test]
+fn test_record_ids_are_unique() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    // Act
+    // Add one entry of each kind for the patient.
+    let allergy_id = contract.add_allergy("Peanuts".to_string(), AllergySeverity::Mild);
+    let record_id = contract.add_medical_record(
+        "Type 1 Diabetes".to_string(),
+        "ipfs://record".to_string(),
+        "2022-01-01".to_string(),
+    );
+    let treatment_id = contract.add_treatment(
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2022-01-01".to_string(),
+        "2023-01-01".to_string(),
+        None,
+        false,
+        None,
+    );
+
+    // Another patient adds an entry with the same content.
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.add_patient(
+        "Jane Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Female,
+        BloodType::ONegative,
+    );
+    let other_allergy_id = contract.add_allergy("Peanuts".to_string(), AllergySeverity::Mild);
+
+    // Assert
+    // IDs are assigned in increasing order, across record kinds and patients.
+    assert_eq!(
+        vec![allergy_id, record_id, treatment_id, other_allergy_id],
+        vec![0, 1, 2, 3]
+    );
+
+    // The stored entries carry the returned IDs.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    let patient = contract.get_patient();
+    assert_eq!(patient.allergies()[0].id(), allergy_id);
+    assert_eq!(patient.records()[0].id(), record_id);
+    assert_eq!(patient.records()[1].id(), treatment_id);
+}
+
+#[test]
+fn test_provider_registration_and_roles() {
+    // Arrange
+    // Create a new context with the test account.
+    let context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+
+    // Act
+    // Register the calling account as a doctor.
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+
+    // Assert
+    // Assert that the provider was registered with the correct details.
+    let provider = contract.get_provider(accounts(2));
+    assert_eq!(provider.full_name(), "Dr. Alice Smith".to_string());
+    assert!(provider.role() == ProviderRole::Doctor);
+    assert_eq!(provider.license_number(), "MD-12345".to_string());
+    assert_eq!(provider.organization(), "General Hospital".to_string());
+
+    // Assert that the role checks behave as expected.
+    assert!(contract.is_provider(accounts(2), None));
+    assert!(contract.is_provider(accounts(2), Some(ProviderRole::Doctor)));
+    assert!(!contract.is_provider(accounts(2), Some(ProviderRole::Pharmacist)));
+    assert!(!contract.is_provider(accounts(1), None));
+
+    // Deregister the provider and assert it is no longer known.
+    contract.deregister_provider();
+    assert!(!contract.is_provider(accounts(2), None));
+}
+
+#[test]
+#[should_panic(expected = "Provider role is not authorized for this action.")]
+fn test_provider_role_check_rejects_other_roles() {
+    // Arrange
+    // Create a new context with the test account.
+    let context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.register_provider(
+        "Bob Jones".to_string(),
+        ProviderRole::Nurse,
+        "RN-777".to_string(),
+        "City Clinic".to_string(),
+    );
+
+    // Act & Assert
+    // A nurse must not pass a check restricted to doctors and pharmacists.
+    contract.assert_provider_role(
+        &accounts(2),
+        &[ProviderRole::Doctor, ProviderRole::Pharmacist],
+    );
+}
+
+#[test]
+fn test_grant_and_revoke_access() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    // Act
+    // Grant read access to the doctor's account.
+    contract.grant_access(accounts(2), vec![AccessScope::Demographics], None);
+
+    // Assert
+    // Assert that the grant is listed for the patient.
+    let grants = contract.list_grants();
+    assert_eq!(grants.len(), 1);
+    assert_eq!(grants[0].grant().grantee(), accounts(2));
+    assert!(grants[0].status() == GrantStatus::Active);
+
+    // Switch to the doctor's account and read the patient information.
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    // The grantee registers for storage, as the audit log entries of their reads are charged to them.
+    contract.storage_deposit(None, None);
+    let patient = contract.get_patient_for(accounts(1));
+    assert_eq!(patient.full_name(), Some("Jack Johnson".to_string()));
+
+    // Switch back to the patient and revoke the access.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.revoke_access(accounts(2));
+    assert!(contract.list_grants().is_empty());
+    assert!(!contract.has_access(&accounts(1), &accounts(2)));
+}
+
+#[test]
+#[should_panic(expected = "Account is not registered for storage. Call storage_deposit first.")]
+fn test_get_patient_for_requires_storage_registration() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.grant_access(accounts(2), vec![AccessScope::Demographics], None);
+
+    // Act & Assert
+    // The grantee reads without a storage deposit to pay for the audit log entry.
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.get_patient_for(accounts(1));
+}
+
+#[test]
+#[should_panic(expected = "Access not granted by patient.")]
+fn test_get_patient_for_without_grant() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    // Act & Assert
+    // Another account without a grant must not be able to read the patient.
+    testing_env!(context.predecessor_account_id(accounts(3)).build());
+    contract.get_patient_for(accounts(1));
+}
+
+#[test]
+fn test_time_limited_access_expires() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain at a known block timestamp
+    testing_env!(context.block_timestamp(1_000).build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    // Act
+    // Grant the clinic access for a 72 hour visit.
+    contract.grant_access(accounts(2), vec![AccessScope::Demographics], Some(72));
+
+    // Assert
+    // The grant authorizes reads just before it expires.
+    let expires_at = 1_000 + 72 * 3_600_000_000_000;
+    testing_env!(context
+        .predecessor_account_id(accounts(2))
+        .block_timestamp(expires_at - 1)
+        .build());
+    // The grantee registers for storage, as the audit log entries of their reads are charged to them.
+    contract.storage_deposit(None, None);
+    assert_eq!(
+        contract.get_patient_for(accounts(1)).full_name(),
+        Some("Jack Johnson".to_string())
+    );
+
+    // Once the grant expires it no longer authorizes reads.
+    testing_env!(context.block_timestamp(expires_at).build());
+    assert!(!contract.has_access(&accounts(1), &accounts(2)));
+
+    // The expired grant is still listed, marked as expired.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    let grants = contract.list_grants();
+    assert_eq!(grants.len(), 1);
+    assert_eq!(grants[0].grant().expires_at(), Some(expires_at));
+    assert!(grants[0].status() == GrantStatus::Expired);
+
+    // The next change to the patient's grants prunes the expired grant.
+    contract.grant_access(accounts(3), vec![AccessScope::Allergies], None);
+    let grants = contract.list_grants();
+    assert_eq!(grants.len(), 1);
+    assert_eq!(grants[0].grant().grantee(), accounts(3));
+}
+
+#[test]
+fn test_scoped_access_filters_patient_view() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_allergy("Peanuts".to_string(), AllergySeverity::Mild);
+    contract.add_procedure(
+        "Appendectomy".to_string(),
+        "2021-01-01".to_string(),
+        "N/A".to_string(),
+    );
+    contract.add_treatment(
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2022-01-01".to_string(),
+        "2023-01-01".to_string(),
+        None,
+        false,
+        None,
+    );
+    contract.add_family_medical_record("Diabetes".to_string(), "Father".to_string(), false, None);
+
+    // Act
+    // Grant the pharmacist access to allergies and treatments only.
+    contract.grant_access(
+        accounts(2),
+        vec![AccessScope::Allergies, AccessScope::Treatments],
+        None,
+    );
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    // The grantee registers for storage, as the audit log entries of their reads are charged to them.
+    contract.storage_deposit(None, None);
+    let patient = contract.get_patient_for(accounts(1));
+
+    // Assert
+    // Assert that only the granted sections are visible.
+    assert_eq!(patient.full_name(), None);
+    assert_eq!(patient.blood_type(), None);
+    assert_eq!(patient.allergies().unwrap().len(), 1);
+    assert!(patient.immunizations().is_none());
+    assert!(patient.procedures().is_none());
+
+    // Assert that the family medical record is filtered out of the records.
+    let records = patient.records().unwrap();
+    assert_eq!(records.len(), 1);
+    assert!(matches!(records[0], RecordType::Treatment(_)));
+}
+
+#[test]
+fn test_emergency_access_is_logged_for_patient() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_allergy("Penicillin".to_string(), AllergySeverity::Severe);
+
+    // Register an emergency provider.
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.register_provider(
+        "Dr. Eve Adams".to_string(),
+        ProviderRole::Emergency,
+        "ER-001".to_string(),
+        "City ER".to_string(),
+    );
+    // An admin verifies the provider.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.verify_provider(accounts(2));
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+
+    // Act
+    // Use break-glass access on the unconscious patient.
+    let view = contract.emergency_access(accounts(1), "Unconscious on arrival".to_string());
+
+    // Assert
+    // Assert that the critical information was returned.
+    assert_eq!(view.blood_type(), BloodType::APositive);
+    assert_eq!(view.allergies()[0].allergen(), "Penicillin".to_string());
+    assert!(view.treatments().is_empty());
+
+    // Assert that the access is surfaced to the patient.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    let accesses = contract.get_emergency_accesses();
+    assert_eq!(accesses.len(), 1);
+    assert_eq!(accesses[0].provider(), accounts(2));
+    assert_eq!(
+        accesses[0].justification(),
+        "Unconscious on arrival".to_string()
+    );
+}
+
+#[test]
+#[should_panic(expected = "Emergency access rate limit exceeded.")]
+fn test_emergency_access_rate_limit() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.register_provider(
+        "Dr. Eve Adams".to_string(),
+        ProviderRole::Emergency,
+        "ER-001".to_string(),
+        "City ER".to_string(),
+    );
+    // An admin verifies the provider.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.verify_provider(accounts(2));
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+
+    // Act & Assert
+    // The sixth access within 24 hours must be rejected.
+    for _ in 0..6 {
+        contract.emergency_access(accounts(1), "Cardiac arrest".to_string());
+    }
+}
+
+#[test]
+#[should_panic(expected = "Emergency access has been suspended for this provider.")]
+fn test_emergency_access_suspended_by_admin() {
+    // Arrange
+    // Create a new context with the admin account, which initializes the contract.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.register_provider(
+        "Dr. Eve Adams".to_string(),
+        ProviderRole::Emergency,
+        "ER-001".to_string(),
+        "City ER".to_string(),
+    );
+    // An admin verifies the provider.
+    testing_env!(context.predecessor_account_id(accounts(0)).build());
+    contract.verify_provider(accounts(2));
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+
+    // Act
+    // The admin suspends the provider's break-glass access.
+    testing_env!(context.predecessor_account_id(accounts(0)).build());
+    contract.suspend_emergency_access(accounts(2));
+
+    // Assert
+    // The suspended provider can no longer use break-glass access.
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.emergency_access(accounts(1), "Unconscious on arrival".to_string());
+}
+
+#[test]
+fn test_access_log_records_reads_and_writes() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.block_timestamp(100).build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_allergy("Peanuts".to_string(), AllergySeverity::Mild);
+    contract.grant_access(accounts(2), vec![AccessScope::Allergies], None);
+
+    // Act
+    // The grantee reads the patient's allergies.
+    testing_env!(context
+        .predecessor_account_id(accounts(2))
+        .block_timestamp(200)
+        .build());
+    // The grantee registers for storage, as the audit log entries of their reads are charged to them.
+    contract.storage_deposit(None, None);
+    contract.get_patient_for(accounts(1));
+
+    // Assert
+    // Assert that every read and write was recorded, oldest first.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    let log = contract.get_access_log(0, 10);
+    let methods: Vec<String> = log.iter().map(|entry| entry.method()).collect();
+    assert_eq!(
+        methods,
+        vec![
+            "add_patient",
+            "add_allergy",
+            "grant_access",
+            "get_patient_for"
+        ]
+    );
+
+    let read = &log[3];
+    assert_eq!(read.actor(), accounts(2));
+    assert_eq!(read.timestamp(), 200);
+    assert!(read.sections() == vec![AccessScope::Allergies]);
+
+    // Assert that the log can be paged through.
+    let page = contract.get_access_log(1, 2);
+    assert_eq!(page.len(), 2);
+    assert_eq!(page[0].method(), "add_allergy".to_string());
+    assert!(contract.get_access_log(4, 10).is_empty());
+}
+
+#[test]
+fn test_guardian_manages_dependant_until_majority() {
+    // Arrange
+    // Create a new context with the parent's account.
+    let mut context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.block_timestamp(1_000).build());
+
+    let mut contract = Contract::new();
+    // The guardian pays for the storage of the registration request.
+    contract.storage_deposit(None, None);
+
+    // Act
+    // The parent registers their child, the child's account accepts,
+    // and the parent records an immunization for them.
+    contract.register_dependant(
+        accounts(3),
+        "Timmy Johnson".to_string(),
+        "2015-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    testing_env!(context.predecessor_account_id(accounts(3)).build());
+    contract.accept_guardianship(accounts(2));
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.add_immunization_for(accounts(3), "Measles".to_string(), "2016-01-01".to_string());
+
+    // Assert
+    // Assert that the immunization was added to the child's record.
+    testing_env!(context.predecessor_account_id(accounts(3)).build());
+    let patient = contract.get_patient();
+    assert_eq!(patient.immunizations()[0].name(), "Measles".to_string());
+    assert_eq!(contract.get_guardians(), vec![accounts(2)]);
+
+    // Assert that the write was attributed to the parent in the audit log.
+    let log = contract.get_access_log(0, 10);
+    assert_eq!(log[1].actor(), accounts(2));
+    assert_eq!(log[1].method(), "add_immunization_for".to_string());
+
+    // The child takes over control once they turn 18 (2033-05-18 is after 2033-02-01).
+    testing_env!(context.block_timestamp(2_000_000_000_000_000_000).build());
+    contract.take_control();
+    assert!(contract.get_guardians().is_empty());
+    assert!(!contract.is_guardian(&accounts(3), &accounts(2)));
+    let log = contract.get_access_log(0, 10);
+    assert_eq!(log[2].actor(), accounts(3));
+    assert_eq!(log[2].method(), "take_control".to_string());
+}
+
+#[test]
+#[should_panic(expected = "Patient has not reached majority yet.")]
+fn test_dependant_cannot_take_control_before_majority() {
+    // Arrange
+    // Create a new context with the parent's account.
+    let mut context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.block_timestamp(1_000).build());
+
+    let mut contract = Contract::new();
+    // The guardian pays for the storage of the registration request.
+    contract.storage_deposit(None, None);
+    contract.register_dependant(
+        accounts(3),
+        "Timmy Johnson".to_string(),
+        "2015-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    testing_env!(context.predecessor_account_id(accounts(3)).build());
+    contract.accept_guardianship(accounts(2));
+
+    // Act & Assert
+    // The child cannot take over control while still a minor.
+    contract.take_control();
+}
+
+#[test]
+fn test_dependant_registration_requires_acceptance() {
+    // Arrange
+    // Create a new context with an account claiming to be the guardian.
+    let mut context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.block_timestamp(1_000).build());
+
+    let mut contract = Contract::new();
+    // The guardian pays for the storage of the registration request.
+    contract.storage_deposit(None, None);
+    let guardian_balance = get_available_balance(&contract, accounts(2));
+
+    // Act
+    // The account asks to register another account as its dependant.
+    contract.register_dependant(
+        accounts(3),
+        "Timmy Johnson".to_string(),
+        "2015-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    // Assert
+    // The request has no effect until the dependant's account accepts it.
+    assert!(!contract.is_guardian(&accounts(3), &accounts(2)));
+    assert!(get_available_balance(&contract, accounts(2)) < guardian_balance);
+    testing_env!(context.predecessor_account_id(accounts(3)).build());
+    let pending = contract.get_pending_guardianships();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].guardian_id(), accounts(2));
+
+    // The account owner declines it, refunding the deposit, and registers themselves.
+    testing_env!(context.predecessor_account_id(accounts(3)).build());
+    contract.decline_guardianship(accounts(2));
+    let receipts = get_created_receipts();
+    assert_eq!(receipts[0].receiver_id, accounts(2));
+    assert!(matches!(
+        receipts[0].actions[0],
+        VmAction::Transfer { deposit } if deposit == STORAGE_DEPOSIT
+    ));
+    assert_eq!(
+        get_available_balance(&contract, accounts(2)),
+        guardian_balance
+    );
+    contract.add_patient(
+        "Tim Johnson".to_string(),
+        "2001-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    assert!(contract.get_guardians().is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Full name is too long.")]
+fn test_dependant_registration_rejects_long_name() {
+    // Arrange
+    // Create a new context with the parent's account.
+    let mut context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.block_timestamp(1_000).build());
+
+    let mut contract = Contract::new();
+    contract.storage_deposit(None, None);
+
+    // Act & Assert
+    // The name of a dependant is capped, as the guardian pays for it.
+    contract.register_dependant(
+        accounts(3),
+        "T".repeat(101),
+        "2015-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Only guardians of the patient can call this method.")]
+fn test_non_guardian_cannot_write_for_patient() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "1950-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_guardian(accounts(2));
+
+    // Act & Assert
+    // An account that is not a guardian cannot add allergies for the patient.
+    testing_env!(context.predecessor_account_id(accounts(4)).build());
+    contract.add_allergy_for(accounts(1), "Peanuts".to_string(), AllergySeverity::Mild);
+}
+
+#[test]
+fn test_provider_submitted_record_accepted_with_author() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.grant_access(accounts(2), vec![AccessScope::Treatments], None);
+
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    // An admin verifies the provider.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.verify_provider(accounts(2));
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+
+    // Act
+    // The doctor submits a treatment, which the patient accepts.
+    let pending_id = contract.submit_treatment(
+        accounts(1),
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2022-01-01".to_string(),
+        "2023-01-01".to_string(),
+        None,
+        None,
+        None,
+    );
+
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    assert_eq!(contract.get_pending_records().len(), 1);
+    contract.accept_record(pending_id);
+
+    // Assert
+    // Assert that the treatment was added with the doctor as its author.
+    assert!(contract.get_pending_records().is_empty());
+    let records = contract.get_patient().records();
+    assert_eq!(records.len(), 1);
+    match &records[0] {
+        RecordType::Treatment(treatment) => assert_eq!(treatment.author(), Some(accounts(2))),
+        _ => panic!("Expected a treatment"),
+    }
+}
+
+#[test]
+fn test_provider_submitted_record_rejected() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.grant_access(accounts(2), vec![AccessScope::Procedures], None);
+
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    // An admin verifies the provider.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.verify_provider(accounts(2));
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    let pending_id = contract.submit_procedure(
+        accounts(1),
+        "Appendectomy".to_string(),
+        "2021-01-01".to_string(),
+        "N/A".to_string(),
+    );
+
+    // Act
+    // The patient rejects the submitted procedure.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.reject_record(pending_id);
+
+    // Assert
+    // Assert that the procedure was discarded.
+    assert!(contract.get_pending_records().is_empty());
+    assert!(contract.get_patient().procedures().is_empty());
+}
+
+#[test]
+fn test_provider_submission_charged_to_provider() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.grant_access(accounts(2), vec![AccessScope::Treatments], None);
+
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    // An admin verifies the provider.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.verify_provider(accounts(2));
+    let patient_balance = get_available_balance(&contract, accounts(1));
+    let provider_balance = get_available_balance(&contract, accounts(2));
+
+    // Act
+    // The doctor submits a treatment for the patient.
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    let pending_id = contract.submit_treatment(
+        accounts(1),
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2022-01-01".to_string(),
+        "2023-01-01".to_string(),
+        None,
+        None,
+        None,
+    );
+
+    // Assert
+    // Assert that the provider pays for the pending record, not the patient.
+    assert_eq!(
+        get_available_balance(&contract, accounts(1)),
+        patient_balance
+    );
+    assert!(get_available_balance(&contract, accounts(2)) < provider_balance);
+
+    // Assert that the record's storage moves to the patient once they accept it.
+    let provider_balance = get_available_balance(&contract, accounts(2));
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.accept_record(pending_id);
+    assert!(get_available_balance(&contract, accounts(1)) < patient_balance);
+    assert!(get_available_balance(&contract, accounts(2)) > provider_balance);
+}
+
+#[test]
+fn test_provider_reclaims_expired_record() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.grant_access(accounts(2), vec![AccessScope::Procedures], None);
+
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    // An admin verifies the provider.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.verify_provider(accounts(2));
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    let pending_id = contract.submit_procedure(
+        accounts(1),
+        "Appendectomy".to_string(),
+        "2021-01-01".to_string(),
+        "N/A".to_string(),
+    );
+    let provider_balance = get_available_balance(&contract, accounts(2));
+
+    // Act
+    // The doctor reclaims the record once the patient let it expire.
+    testing_env!(context.block_timestamp(31 * 24 * 3_600_000_000_000).build());
+    contract.reclaim_expired_record(accounts(1), pending_id);
+
+    // Assert
+    // Assert that the record was discarded and its storage refunded.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    assert!(contract.get_pending_records().is_empty());
+    assert!(contract.get_patient().procedures().is_empty());
+    assert!(get_available_balance(&contract, accounts(2)) > provider_balance);
+}
+
+#[test]
+#[should_panic(expected = "Access not granted by patient.")]
+fn test_provider_cannot_submit_outside_granted_scopes() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.grant_access(accounts(2), vec![AccessScope::Allergies], None);
+
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    // An admin verifies the provider.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.verify_provider(accounts(2));
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+
+    // Act & Assert
+    // The doctor was only granted allergies and cannot submit medical records.
+    contract.submit_medical_record(
+        accounts(1),
+        "Type 1 Diabetes".to_string(),
+        "ipfs://record".to_string(),
+        "2022-01-01".to_string(),
+        None,
+        None,
+    );
+}
+
+#[test]
+fn test_signed_treatment_verified_on_insertion() {
+    // Arrange
+    // Create a new context with the provider account.
+    let mut context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    let keypair = get_signing_keypair();
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    contract.set_signing_key(to_near_public_key(&keypair.public));
+    // An admin verifies the provider.
+    contract.verify_provider(accounts(2));
+
+    // The provider signs the treatment they issued off-chain.
+    // The ID is assigned by the contract and left out of the signature.
+    let treatment = Treatment::new(
+        0,
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2022-01-01".parse().unwrap(),
+        "2023-01-01".parse().unwrap(),
+        None,
+    )
+    .with_author(accounts(2));
+    let signature = keypair
+        .sign(&treatment.signing_payload(&accounts(1), 1))
+        .to_bytes()
+        .to_vec();
+
+    // Act
+    // The patient adds the signed treatment to their records.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    let id = contract.add_signed_treatment(
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2022-01-01".to_string(),
+        "2023-01-01".to_string(),
+        None,
+        accounts(2),
+        Base64VecU8(signature.clone()),
+        1,
+    );
+
+    // Assert
+    // Assert that the stored treatment carries the provider's signature.
+    match &contract.get_patient().records()[0] {
+        RecordType::Treatment(treatment) => {
+            assert_eq!(treatment.author(), Some(accounts(2)));
+            assert_eq!(treatment.signature(), Some(signature));
+        }
+        _ => panic!("Expected a treatment"),
+    }
+
+    // The nonce needed to re-verify the signature is kept with the treatment's ID.
+    assert_eq!(contract.get_signature_nonce(id), Some(1));
+
+    // So is the signing key, which outlives a rotation of the provider's key.
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    let rotated_key = Ed25519PublicKey::from(&SecretKey::from_bytes(&[8u8; 32]).unwrap());
+    contract.set_signing_key(to_near_public_key(&rotated_key));
+    assert_eq!(
+        contract.get_record_signing_key(id),
+        Some(to_near_public_key(&keypair.public))
+    );
+}
+
+#[test]
+#[should_panic(expected = "Provider is not verified.")]
+fn test_signed_record_by_unverified_provider_rejected() {
+    // Arrange
+    // Create a new context with the provider account.
+    let mut context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    let keypair = get_signing_keypair();
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    contract.set_signing_key(to_near_public_key(&keypair.public));
+
+    // The provider, not yet verified by an admin, signs a treatment off-chain.
+    let treatment = Treatment::new(
+        0,
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2022-01-01".parse().unwrap(),
+        "2023-01-01".parse().unwrap(),
+        None,
+    )
+    .with_author(accounts(2));
+    let signature = keypair
+        .sign(&treatment.signing_payload(&accounts(1), 1))
+        .to_bytes()
+        .to_vec();
+
+    // Act & Assert
+    // The signature of an unverified provider is not accepted.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_signed_treatment(
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2022-01-01".to_string(),
+        "2023-01-01".to_string(),
+        None,
+        accounts(2),
+        Base64VecU8(signature),
+        1,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Signature nonce already used.")]
+fn test_replayed_signed_record_rejected() {
+    // Arrange
+    // Create a new context with the provider account.
+    let mut context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    let keypair = get_signing_keypair();
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    contract.set_signing_key(to_near_public_key(&keypair.public));
+    // An admin verifies the provider.
+    contract.verify_provider(accounts(2));
+
+    // The provider signs a record for the patient off-chain.
+    let record = MedicalRecord::new(
+        0,
+        "Type 1 Diabetes".to_string(),
+        "ipfs://record".to_string(),
+        "2022-01-01".parse().unwrap(),
+    )
+    .with_author(accounts(2));
+    let signature = keypair
+        .sign(&record.signing_payload(&accounts(1), 1))
+        .to_bytes()
+        .to_vec();
+
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_signed_medical_record(
+        "Type 1 Diabetes".to_string(),
+        "ipfs://record".to_string(),
+        "2022-01-01".to_string(),
+        accounts(2),
+        Base64VecU8(signature.clone()),
+        1,
+    );
+
+    // Act & Assert
+    // The patient adds the same signed record again, which must be rejected.
+    contract.add_signed_medical_record(
+        "Type 1 Diabetes".to_string(),
+        "ipfs://record".to_string(),
+        "2022-01-01".to_string(),
+        accounts(2),
+        Base64VecU8(signature),
+        1,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Invalid record signature.")]
+fn test_signed_record_for_another_patient_rejected() {
+    // Arrange
+    // Create a new context with the provider account.
+    let mut context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    let keypair = get_signing_keypair();
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    contract.set_signing_key(to_near_public_key(&keypair.public));
+    // An admin verifies the provider.
+    contract.verify_provider(accounts(2));
+
+    // The provider signs a record for a different patient.
+    let record = MedicalRecord::new(
+        0,
+        "Type 1 Diabetes".to_string(),
+        "ipfs://record".to_string(),
+        "2022-01-01".parse().unwrap(),
+    )
+    .with_author(accounts(2));
+    let signature = keypair
+        .sign(&record.signing_payload(&accounts(3), 1))
+        .to_bytes()
+        .to_vec();
+
+    // Act & Assert
+    // The patient adds the record signed for someone else, which must fail verification.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_signed_medical_record(
+        "Type 1 Diabetes".to_string(),
+        "ipfs://record".to_string(),
+        "2022-01-01".to_string(),
+        accounts(2),
+        Base64VecU8(signature),
+        1,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Invalid record signature.")]
+fn test_tampered_signed_record_rejected() {
+    // Arrange
+    // Create a new context with the provider account.
+    let mut context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    let keypair = get_signing_keypair();
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    contract.set_signing_key(to_near_public_key(&keypair.public));
+    // An admin verifies the provider.
+    contract.verify_provider(accounts(2));
+
+    // The provider signs a record with a different condition.
+    let record = MedicalRecord::new(
+        0,
+        "Type 2 Diabetes".to_string(),
+        "ipfs://record".to_string(),
+        "2022-01-01".parse().unwrap(),
+    )
+    .with_author(accounts(2));
+    let signature = keypair
+        .sign(&record.signing_payload(&accounts(1), 1))
+        .to_bytes()
+        .to_vec();
+
+    // Act & Assert
+    // The patient tampers with the condition, which must fail verification.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_signed_medical_record(
+        "Type 1 Diabetes".to_string(),
+        "ipfs://record".to_string(),
+        "2022-01-01".to_string(),
+        accounts(2),
+        Base64VecU8(signature),
+        1,
+    );
+}
+
+#[test]
+#[should_panic(expected = "Invalid birthday: '30' is not an ISO-8601 date (YYYY-MM-DD).")]
+fn test_patient_creation_invalid_birthday() {
+    // Arrange
+    // Create a new context with the test account.
+    let context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+
+    // Act & Assert
+    // A birthday that is not an ISO-8601 date must be rejected.
+    contract.add_patient(
+        "Prakhar".to_string(),
+        "30".to_string(),
+        Gender::Male,
+        BloodType::OPositive,
+    );
+}
+
+#[test]
+fn test_date_parsing_and_ordering() {
+    // Valid ISO-8601 dates are parsed and compared chronologically.
+    let earlier: Date = "2023-07-30".parse().unwrap();
+    let later: Date = "2024-02-29".parse().unwrap();
+    assert!(earlier < later);
+    assert_eq!(later.to_string(), "2024-02-29".to_string());
+
+    // Malformed dates and dates missing from the calendar are rejected.
+    assert!("30/07/2023".parse::<Date>().is_err());
+    assert!("2023-02-29".parse::<Date>().is_err());
+    assert!("2023-13-01".parse::<Date>().is_err());
+
+    // Block timestamps convert to their UTC calendar date.
+    assert_eq!(
+        Date::from_timestamp(0).to_string(),
+        "1970-01-01".to_string()
+    );
+    assert_eq!(
+        Date::from_timestamp(1_709_164_800_000_000_000).to_string(),
+        "2024-02-29".to_string()
+    );
+
+    // Ages are counted in full years.
+    let birthday: Date = "2000-02-29".parse().unwrap();
+    assert_eq!(birthday.years_until(&"2018-02-28".parse().unwrap()), 17);
+    assert_eq!(birthday.add_years(18).to_string(), "2018-02-28".to_string());
+
+    // Legacy day-first dates are converted, other free-form values are not.
+    assert_eq!(
+        Date::from_legacy("30/07/2023").map(|date| date.to_string()),
+        Some("2023-07-30".to_string())
+    );
+    assert!(Date::from_legacy("30").is_none());
+}
+
+#[test]
+fn test_migrate_legacy_patient_dates() {
+    // Arrange
+    // Create a new context with the admin account, which initializes the contract.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+
+    // Store patients in the layout used before dates were validated.
+    let legacy_patient = |birthday: &str| LegacyPatient {
+        full_name: "Prakhar".to_string(),
+        birthday: birthday.to_string(),
+        gender: "male".to_string(),
+        blood_type: "0+".to_string(),
+        records: vec![],
+        allergies: vec![],
+        immunizations: vec![LegacyImmunization {
+            name: "Flu Shot".to_string(),
+            date: "30/07/2023".to_string(),
+        }],
+        procedures: vec![],
+    };
+    for (account_id, birthday) in [(accounts(1), "30/07/1995"), (accounts(2), "30")] {
+        let key = [b"patients".as_slice(), &account_id.try_to_vec().unwrap()].concat();
+        env::storage_write(&key, &legacy_patient(birthday).try_to_vec().unwrap());
+    }
+
+    // Act
+    // Migrate both patients.
+    let failed = contract.migrate_legacy_patients(vec![accounts(1), accounts(2)]);
+
+    // Assert
+    // The patient with an unconvertible birthday is reported.
+    assert_eq!(failed, vec![accounts(2)]);
+
+    // The other patient can be read with validated dates.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    let patient = contract.get_patient();
+    assert_eq!(patient.birthday().to_string(), "1995-07-30".to_string());
+    assert_eq!(
+        patient.immunizations()[0].date().to_string(),
+        "2023-07-30".to_string()
+    );
+
+    // Free-form demographics are mapped to their typed values.
+    assert_eq!(patient.gender(), Gender::Male);
+    assert_eq!(patient.blood_type(), BloodType::OPositive);
+}
+
+#[test]
+fn test_correct_legacy_patient() {
+    // Arrange
+    // Create a new context with the admin account, which initializes the contract.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+
+    // Store a patient whose birthday can't be converted.
+    let legacy_patient = LegacyPatient {
+        full_name: "Prakhar".to_string(),
+        birthday: "30".to_string(),
+        gender: "male".to_string(),
+        blood_type: "0+".to_string(),
+        records: vec![],
+        allergies: vec![],
+        immunizations: vec![LegacyImmunization {
+            name: "Flu Shot".to_string(),
+            date: "30/07/2023".to_string(),
+        }],
+        procedures: vec![],
+    };
+    let key = [b"patients".as_slice(), &accounts(1).try_to_vec().unwrap()].concat();
+    env::storage_write(&key, &legacy_patient.try_to_vec().unwrap());
+    assert_eq!(
+        contract.migrate_legacy_patients(vec![accounts(1)]),
+        vec![accounts(1)]
+    );
+
+    // Act
+    // The patient supplies the corrected birthday.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.correct_legacy_patient(
+        accounts(1),
+        HashMap::from([("30".to_string(), "1995-07-30".to_string())]),
+    );
+
+    // Assert
+    // The patient can be read with the corrected and converted dates.
+    let patient = contract.get_patient();
+    assert_eq!(patient.birthday().to_string(), "1995-07-30".to_string());
+    assert_eq!(
+        patient.immunizations()[0].date().to_string(),
+        "2023-07-30".to_string()
+    );
+}
+
+#[test]
+fn test_demographics_parsing() {
+    // Common spellings are mapped by the lenient parsers.
+    assert_eq!("0+".parse(), Ok(BloodType::OPositive));
+    assert_eq!("ab neg".parse(), Ok(BloodType::ABNegative));
+    assert_eq!("A Rh+".parse(), Ok(BloodType::APositive));
+    assert_eq!("B positive".parse(), Ok(BloodType::BPositive));
+    assert_eq!(" F ".parse(), Ok(Gender::Female));
+    assert_eq!("Non-Binary".parse(), Ok(Gender::Other));
+    assert_eq!("prefer not to say".parse(), Ok(Gender::Undisclosed));
+    assert_eq!("high".parse(), Ok(AllergySeverity::Severe));
+    assert_eq!(
+        "Life-threatening".parse(),
+        Ok(AllergySeverity::LifeThreatening)
+    );
+
+    // Unknown values are rejected.
+    assert!("C+".parse::<BloodType>().is_err());
+    assert!("AB".parse::<BloodType>().is_err());
+    assert!("banana".parse::<Gender>().is_err());
+    assert!("extreme".parse::<AllergySeverity>().is_err());
+
+    // Contract arguments only accept the canonical representations.
+    assert_eq!(
+        near_sdk::serde_json::to_string(&BloodType::ABNegative).unwrap(),
+        "\"AB-\"".to_string()
+    );
+    assert!(near_sdk::serde_json::from_str::<BloodType>("\"O+\"").is_ok());
+    assert!(near_sdk::serde_json::from_str::<BloodType>("\"0+\"").is_err());
+    assert!(near_sdk::serde_json::from_str::<Gender>("\"male\"").is_err());
+    assert!(near_sdk::serde_json::from_str::<AllergySeverity>("\"Severe\"").is_ok());
+}
+
+#[test]
+fn test_amend_record_keeps_history() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    let allergy_id = contract.add_allergy("Peanuts".to_string(), AllergySeverity::Mild);
+
+    // Act
+    // Amend the allergy twice.
+    testing_env!(context.block_timestamp(1_000).build());
+    contract.amend_allergy(
+        allergy_id,
+        "Peanuts".to_string(),
+        AllergySeverity::Severe,
+        "Reaction reassessed".to_string(),
+    );
+    testing_env!(context.block_timestamp(2_000).build());
+    contract.amend_allergy(
+        allergy_id,
+        "Tree nuts".to_string(),
+        AllergySeverity::Severe,
+        "Allergen misidentified".to_string(),
+    );
+
+    // Assert
+    // The patient's information holds the latest version.
+    let patient = contract.get_patient();
+    assert_eq!(patient.allergies().len(), 1);
+    assert_eq!(patient.allergies()[0].allergen(), "Tree nuts".to_string());
+
+    // Every version is kept, with the time, author and reason of each amendment.
+    let history = contract.get_record_history(allergy_id);
+    assert_eq!(history.len(), 3);
+    assert_eq!(history[0].version(), 1);
+    assert_eq!(history[0].amended_at(), None);
+    match history[0].record() {
+        AmendableRecord::Allergy(allergy) => {
+            assert_eq!(allergy.severity(), AllergySeverity::Mild)
+        }
+        _ => panic!("Expected an allergy"),
+    }
+    assert_eq!(history[1].amended_at(), Some(1_000));
+    assert_eq!(history[1].amended_by(), Some(accounts(1)));
+    assert_eq!(history[1].reason(), Some("Reaction reassessed".to_string()));
+    assert_eq!(history[2].version(), 3);
+    assert_eq!(history[2].amended_at(), Some(2_000));
+    assert_eq!(history[2].record().id(), allergy_id);
+}
+
+#[test]
+fn test_amend_signed_record_drops_signature() {
+    // Arrange
+    // Create a new context with the provider account.
+    let mut context = get_context(accounts(2));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    let keypair = get_signing_keypair();
+    contract.register_provider(
+        "Dr. Alice Smith".to_string(),
+        ProviderRole::Doctor,
+        "MD-12345".to_string(),
+        "General Hospital".to_string(),
+    );
+    contract.set_signing_key(to_near_public_key(&keypair.public));
+    // An admin verifies the provider.
+    contract.verify_provider(accounts(2));
+
+    // The patient adds a treatment signed by the provider.
+    let treatment = Treatment::new(
+        0,
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2022-01-01".parse().unwrap(),
+        "2023-01-01".parse().unwrap(),
+        None,
+    )
+    .with_author(accounts(2));
+    let signature = keypair
+        .sign(&treatment.signing_payload(&accounts(1), 1))
+        .to_bytes()
+        .to_vec();
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    let id = contract.add_signed_treatment(
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2022-01-01".to_string(),
+        "2023-01-01".to_string(),
+        None,
+        accounts(2),
+        Base64VecU8(signature),
+        1,
+    );
+
+    // Act
+    // The patient amends the dosage of the treatment.
+    contract.amend_treatment(
+        id,
+        "Insulin".to_string(),
+        "12 units".to_string(),
+        "2022-01-01".to_string(),
+        "2023-01-01".to_string(),
+        None,
+        "Dosage adjusted".to_string(),
+    );
+
+    // Assert
+    // The amended treatment is unsigned, and its nonce and signing key are dropped.
+    match &contract.get_patient().records()[0] {
+        RecordType::Treatment(treatment) => assert_eq!(treatment.signature(), None),
+        _ => panic!("Expected a treatment"),
+    }
+    assert_eq!(contract.get_signature_nonce(id), None);
+    assert_eq!(contract.get_record_signing_key(id), None);
+}
+
+#[test]
+#[should_panic(expected = "Record not found.")]
+fn test_amend_record_of_other_kind_rejected() {
+    // Arrange
+    // Create a new context with the test account.
+    let context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    let allergy_id = contract.add_allergy("Peanuts".to_string(), AllergySeverity::Mild);
+
+    // Act & Assert
+    // The ID belongs to an allergy, not an immunization.
+    contract.amend_immunization(
+        allergy_id,
+        "Flu Shot".to_string(),
+        "2021-01-01".to_string(),
+        "Wrong entry".to_string(),
+    );
+}
+
+#[test]
+fn test_retract_record_keeps_tombstone() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    let mistaken_id = contract.add_allergy("Peanuts".to_string(), AllergySeverity::Mild);
+    let kept_id = contract.add_allergy("Shellfish".to_string(), AllergySeverity::Severe);
+    let record_id = contract.add_medical_record(
+        "Type 1 Diabetes".to_string(),
+        "ipfs://record".to_string(),
+        "2022-01-01".to_string(),
+    );
+
+    // Act
+    // Retract the mistaken allergy and the medical record.
+    testing_env!(context.block_timestamp(1_000).build());
+    contract.retract_record(mistaken_id, "Entered by mistake".to_string());
+    contract.retract_record(record_id, "Wrong patient".to_string());
+
+    // Assert
+    // Only the other entries remain in the patient's information.
+    let patient = contract.get_patient();
+    assert_eq!(patient.allergies().len(), 1);
+    assert_eq!(patient.allergies()[0].id(), kept_id);
+    assert!(patient.records().is_empty());
+
+    // The retracted entries are kept as tombstones with their reason.
+    let tombstones = contract.get_retracted_records();
+    assert_eq!(tombstones.len(), 2);
+    assert_eq!(tombstones[0].record().id(), mistaken_id);
+    assert_eq!(tombstones[0].retracted_at(), 1_000);
+    assert_eq!(tombstones[0].retracted_by(), accounts(1));
+    assert_eq!(tombstones[0].reason(), "Entered by mistake".to_string());
+    assert!(tombstones[1].record().scope() == AccessScope::MedicalRecords);
+
+    // The retractions are recorded in the audit log.
+    let log = contract.get_access_log(0, 10);
+    assert_eq!(log.last().unwrap().method(), "retract_record".to_string());
+}
+
+#[test]
+fn test_records_stored_outside_patient() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    let key = [b"patients".as_slice(), &accounts(1).try_to_vec().unwrap()].concat();
+    let patient_size = env::storage_read(&key).unwrap().len();
+
+    // Act
+    // Add many entries for the patient, in separate calls to stay under the log limit.
+    for index in 0..20 {
+        testing_env!(context.block_timestamp(index).build());
+        contract.add_allergy(format!("Allergen {}", index), AllergySeverity::Mild);
+        contract.add_immunization(format!("Vaccine {}", index), "2021-01-01".to_string());
+    }
+
+    // Assert
+    // The stored patient value doesn't grow with their history.
+    assert_eq!(env::storage_read(&key).unwrap().len(), patient_size);
+
+    // The entries are still returned in the order they were added.
+    let patient = contract.get_patient();
+    assert_eq!(patient.allergies().len(), 20);
+    assert_eq!(patient.allergies()[0].allergen(), "Allergen 0".to_string());
+    assert_eq!(patient.immunizations()[19].name(), "Vaccine 19".to_string());
+}
+
+#[test]
+fn test_patient_charged_for_storage() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    let balance = contract.storage_balance_of(accounts(1)).unwrap();
+    assert_eq!(balance.total().0, STORAGE_DEPOSIT);
+    assert!(balance.available().0 < STORAGE_DEPOSIT);
+
+    // Act
+    // Add a large medical record, without attaching a deposit.
+    testing_env!(context.attached_deposit(0).build());
+    contract.add_medical_record(
+        "Flu".to_string(),
+        "x".repeat(10_000),
+        "2023-01-02".to_string(),
+    );
+
+    // Assert
+    // The record's bytes are locked from the patient's deposit.
+    let charged = balance.available().0
+        - contract
+            .storage_balance_of(accounts(1))
+            .unwrap()
+            .available()
+            .0;
+    assert!(charged >= 10_000 * env::storage_byte_cost());
+
+    // The available balance can be withdrawn with one yoctoNEAR attached.
+    testing_env!(context.attached_deposit(1).build());
+    let balance = contract.storage_withdraw(None);
+    assert_eq!(balance.available().0, 0);
+    assert!(balance.total().0 > 0);
+}
+
+#[test]
+#[should_panic(expected = "Insufficient storage deposit.")]
+fn test_insufficient_storage_deposit_rejected() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    // Register with the minimum balance only, refunding the rest of the deposit.
+    let balance = contract.storage_deposit(None, Some(true));
+    assert!(balance.total() == contract.storage_balance_bounds().min());
+
+    testing_env!(context.attached_deposit(0).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    // Act
+    // Add a record larger than the deposit can pay for.
+    contract.add_medical_record(
+        "Flu".to_string(),
+        "x".repeat(10_000),
+        "2023-01-02".to_string(),
+    );
+}
+
+#[test]
+fn test_remove_patient_refunds_storage() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    testing_env!(context.attached_deposit(0).build());
+    contract.add_medical_record(
+        "Flu".to_string(),
+        "x".repeat(10_000),
+        "2023-01-02".to_string(),
+    );
+    let balance = contract.storage_balance_of(accounts(1)).unwrap();
+
+    // Act
+    // Remove the patient.
+    contract.remove_patient();
+
+    // Assert
+    // The freed storage is refunded, while the audit log stays paid for.
+    let after = contract.storage_balance_of(accounts(1)).unwrap();
+    assert!(balance.total().0 - after.total().0 >= 10_000 * env::storage_byte_cost());
+    assert_eq!(after.available().0, balance.available().0);
+    assert!(after.available().0 < after.total().0);
+
+    // The account can then unregister, which requires one yoctoNEAR attached.
+    testing_env!(context.attached_deposit(1).build());
+    assert!(contract.storage_unregister(None));
+    assert!(contract.storage_balance_of(accounts(1)).is_none());
+    assert!(!contract.storage_unregister(None));
+}
+
+#[test]
+fn test_migrate_state_from_first_version() {
+    // Arrange
+    // Create a new context with the contract account, which calls `migrate` after an upgrade.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    // Store the state written by the first version of the contract:
+    // the prefixes of its patients map and of its public records vector, holding one record.
+    let state = (b"patients".to_vec(), 1u64, b"public_records".to_vec());
+    env::state_write(&state);
+    let patient = LegacyPatient {
+        full_name: "Prakhar".to_string(),
+        birthday: "30/07/1995".to_string(),
+        gender: "male".to_string(),
+        blood_type: "0+".to_string(),
+        records: vec![],
+        allergies: vec![],
+        immunizations: vec![],
+        procedures: vec![],
+    };
+    let key = [b"patients".as_slice(), &accounts(1).try_to_vec().unwrap()].concat();
+    env::storage_write(&key, &patient.try_to_vec().unwrap());
+    let public_record = LegacyPublicRecord {
+        record_type: LegacyRecordType::FamilyMedicalRecord(LegacyFamilyMedicalRecord {
+            id: 7,
+            condition: "Asthma".to_string(),
+            relation_to_patient: "Father".to_string(),
+        }),
+        birthday: "1995-07-30".to_string(),
+        gender: "F".to_string(),
+    };
+    let key = [b"public_records".as_slice(), &0u64.to_le_bytes()].concat();
+    env::storage_write(&key, &public_record.try_to_vec().unwrap());
+
+    // Act
+    // Migrate the contract state, then the values it holds.
+    let mut contract = Contract::migrate();
+    assert!(contract
+        .migrate_legacy_patients(vec![accounts(1)])
+        .is_empty());
+    assert!(contract.migrate_legacy_public_records(0, 10).is_empty());
+
+    // Assert
+    // The patient and the public record are read in the current layout.
+    let public_records = contract.get_all_public_records();
+    assert_eq!(public_records.len(), 1);
+    assert_eq!(public_records[0].gender(), Gender::Female);
+    assert_eq!(public_records[0].record_type().id(), 0);
+    assert_eq!(
+        contract
+            .get_public_records_by_condition("asthma".to_string(), 0, 10)
+            .len(),
+        1
+    );
+
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    let patient = contract.get_patient();
+    assert_eq!(patient.full_name(), "Prakhar".to_string());
+    assert_eq!(patient.blood_type(), BloodType::OPositive);
+
+    // Migrating the current state again leaves it unchanged.
+    env::state_write(&contract);
+    testing_env!(context.predecessor_account_id(accounts(0)).build());
+    let contract = Contract::migrate();
+    assert_eq!(contract.get_all_public_records().len(), 1);
+    assert!(contract.admins.contains(&accounts(0)));
+    assert_eq!(contract.get_owner(), accounts(0));
+}
+
+#[test]
+fn test_migrate_state_from_second_version() {
+    // Arrange
+    // Create a new context with the contract account, which calls `migrate` after an upgrade.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    // Store the state written by the version that introduced state versions,
+    // holding one admin and one patient.
+    let mut admins = UnorderedSet::new(b"admins".to_vec());
+    admins.insert(&accounts(1));
+    let mut state = ContractV2 {
+        patients: LookupMap::new(b"patients".to_vec()),
+        patient_records: LookupMap::new(b"patient_records".to_vec()),
+        providers: LookupMap::new(b"providers".to_vec()),
+        access_grants: LookupMap::new(b"access_grants".to_vec()),
+        admins,
+        emergency_accesses: LookupMap::new(b"emergency_accesses".to_vec()),
+        emergency_usage: LookupMap::new(b"emergency_usage".to_vec()),
+        emergency_suspended: LookupSet::new(b"emergency_suspended".to_vec()),
+        audit_logs: LookupMap::new(b"audit_logs".to_vec()),
+        guardianships: LookupMap::new(b"guardianships".to_vec()),
+        pending_records: LookupMap::new(b"pending_records".to_vec()),
+        next_pending_id: 0,
+        next_record_id: 5,
+        record_history: LookupMap::new(b"record_history".to_vec()),
+        retracted_records: LookupMap::new(b"retracted_records".to_vec()),
+        public_records: Vector::new(b"public_records".to_vec()),
+        storage_accounts: LookupMap::new(b"storage_accounts".to_vec()),
+    };
+    let demographics = Demographics::new(
+        "Jack Johnson".to_string(),
+        "1995-07-30".parse().unwrap(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    state.patients.insert(&accounts(2), &demographics.into());
+    state
+        .patient_records
+        .insert(&accounts(2), &PatientRecords::new(&accounts(2)));
+    env::state_write(&state);
+    env::storage_write(STATE_VERSION_KEY, &StateVersion::V2.try_to_vec().unwrap());
+
+    // Act
+    // Migrate the contract state.
+    let mut contract = Contract::migrate();
+
+    // Assert
+    // The contract account becomes the owner, and the admins are kept.
+    assert_eq!(contract.get_owner(), accounts(0));
+    assert_eq!(contract.get_admins(), vec![accounts(1)]);
+
+    // The patient is read as before, and new entries get IDs after the stored ones.
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    assert_eq!(
+        contract.get_patient().full_name(),
+        "Jack Johnson".to_string()
+    );
+    contract.storage_deposit(None, None);
+    assert_eq!(
+        contract.add_allergy("Peanuts".to_string(), AllergySeverity::Mild),
+        5
+    );
+
+    // Migrating the current state again leaves it unchanged.
+    env::state_write(&contract);
+    testing_env!(context.predecessor_account_id(accounts(0)).build());
+    let contract = Contract::migrate();
+    assert_eq!(contract.get_admins(), vec![accounts(1)]);
+}
+
+#[test]
+fn test_transfer_ownership() {
+    // Arrange
+    // Create a new context with the account that initializes the contract.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    assert_eq!(contract.get_owner(), accounts(0));
+
+    // Act
+    // Transfer the ownership to another account.
+    contract.transfer_ownership(accounts(1));
+
+    // Assert
+    // The new owner can transfer the ownership, while the previous owner cannot.
+    assert_eq!(contract.get_owner(), accounts(1));
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.transfer_ownership(accounts(2));
+    assert_eq!(contract.get_owner(), accounts(2));
+}
+
+#[test]
+fn test_upgrade_deploys_code_and_migrates() {
+    // Arrange
+    // Create a new context with the account that initializes the contract.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let contract = Contract::new();
+    let code = vec![0, 97, 115, 109, 1, 0, 0, 0];
+
+    // Act
+    // Upgrade the contract with the code passed as input.
+    context.context.input = code.clone();
+    testing_env!(context.build());
+    let _ = contract.upgrade();
+
+    // Assert
+    // The code is deployed to the contract account, then `migrate` is called.
+    let receipts = get_created_receipts();
+    assert_eq!(receipts.len(), 1);
+    assert_eq!(receipts[0].receiver_id, accounts(0));
+    assert_eq!(receipts[0].actions[0], VmAction::DeployContract { code });
+    match &receipts[0].actions[1] {
+        VmAction::FunctionCall { function_name, .. } => assert_eq!(function_name, "migrate"),
+        _ => panic!("Expected a call to migrate."),
+    }
+}
+
+#[test]
+#[should_panic(expected = "Only the owner can call this method.")]
+fn test_upgrade_by_non_owner_rejected() {
+    // Arrange
+    // Create a new context with the account that initializes the contract.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let contract = Contract::new();
+
+    // Act
+    // Try to upgrade the contract from another account.
+    context.context.input = vec![0, 97, 115, 109, 1, 0, 0, 0];
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    let _ = contract.upgrade();
+}
+
+// Parse the NEP-297 events logged since the environment was last set
+fn get_events() -> Vec<near_sdk::serde_json::Value> {
+    get_logs()
+        .iter()
+        .filter_map(|log| log.strip_prefix("EVENT_JSON:"))
+        .map(|event| near_sdk::serde_json::from_str(event).unwrap())
+        .collect()
+}
+
+#[test]
+fn test_events_emitted_without_phi() {
+    // Arrange
+    // Create a new context with the test account.
+    let context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    // Act
+    // Add a public treatment and update the patient's name.
+    testing_env!(context.build());
+    let id = contract.add_treatment(
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2023-01-02".to_string(),
+        "2023-02-02".to_string(),
+        None,
+        true,
+        Some(get_commitment(&accounts(1), "insulin")),
+    );
+    contract.full_name("John Jackson".to_string());
+
+    // Assert
+    // The events follow NEP-297 and identify the changes.
+    let events = get_events();
+    assert_eq!(events.len(), 3);
+    assert_eq!(events[0]["standard"], "medibridge");
+    assert_eq!(events[0]["version"], "1.0.0");
+    assert_eq!(events[0]["event"], "public_record_published");
+    assert_eq!(events[0]["data"]["index"], 0);
+    assert_eq!(events[0]["data"]["section"], "Treatments");
+    assert_eq!(events[1]["event"], "record_added");
+    assert_eq!(events[1]["data"]["patient_id"], accounts(1).to_string());
+    assert_eq!(events[1]["data"]["record_id"], id);
+    assert_eq!(events[2]["event"], "demographics_updated");
+    assert_eq!(events[2]["data"]["field"], "full_name");
+
+    // The public record is not linked to the patient, and no event holds health information.
+    assert!(events[0]["data"].get("patient_id").is_none());
+    let payloads = near_sdk::serde_json::to_string(&events).unwrap();
+    for value in ["Insulin", "10 units", "John Jackson", "2023-01-02"] {
+        assert!(!payloads.contains(value));
+    }
+}
+
+#[test]
+fn test_public_records_paginated() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    for index in 0..5 {
+        testing_env!(context.block_timestamp(index).build());
+        contract.add_public_medical_record(
+            format!("Condition {}", index),
+            "Record data".to_string(),
+            "2023-01-02".to_string(),
+            get_commitment(&accounts(1), &format!("secret {}", index)),
+        );
+    }
+
+    // Act
+    // Read the public records in pages of two.
+    let first_page = contract.get_public_records(0, 2);
+    let last_page = contract.get_public_records(4, 2);
+
+    // Assert
+    // The pages hold the records in the order they were published.
+    assert_eq!(contract.public_records_count(), 5);
+    assert_eq!(first_page.len(), 2);
+    assert_eq!(last_page.len(), 1);
+    assert_eq!(
+        first_page[1].record_type().condition(),
+        Some("Condition 1".to_string())
+    );
+    assert_eq!(
+        last_page[0].record_type().condition(),
+        Some("Condition 4".to_string())
+    );
+
+    // A page past the end is empty, and the page size is capped.
+    assert!(contract.get_public_records(5, 2).is_empty());
+    assert_eq!(contract.get_public_records(0, u64::MAX).len(), 5);
+}
+
+#[test]
+fn test_public_records_filtered_by_index() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "1990-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.add_patient(
+        "Jane Johnson".to_string(),
+        "1992-03-04".to_string(),
+        Gender::Female,
+        BloodType::ONegative,
+    );
+
+    // Publish records of each kind for both patients.
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_public_medical_record(
+        "Type 1 Diabetes".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(1), "diabetes"),
+    );
+    contract.add_treatment(
+        "Insulin".to_string(),
+        "10 units".to_string(),
+        "2023-03-01".to_string(),
+        "2023-04-01".to_string(),
+        None,
+        true,
+        Some(get_commitment(&accounts(1), "insulin")),
+    );
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.add_public_medical_record(
+        "type 1  diabetes".to_string(),
+        "Record data".to_string(),
+        "2023-05-06".to_string(),
+        get_commitment(&accounts(2), "diabetes"),
+    );
+    contract.add_family_medical_record(
+        "Asthma".to_string(),
+        "Father".to_string(),
+        true,
+        Some(get_commitment(&accounts(2), "asthma")),
+    );
+
+    // Act
+    // Filter the public records by kind, condition, gender and date.
+    let treatments = contract.get_public_records_by_kind(RecordKind::Treatment, 0, 10);
+    let diabetes = contract.get_public_records_by_condition("TYPE 1 DIABETES".to_string(), 0, 10);
+    let female = contract.get_public_records_by_gender(Gender::Female, 0, 10);
+    let spring = contract.get_public_records_by_date(
+        "2023-02-01".to_string(),
+        "2023-05-31".to_string(),
+        0,
+        10,
+    );
+
+    // Assert
+    // Each index holds the matching records in the order they were published.
+    assert_eq!(treatments.len(), 1);
+    assert_eq!(treatments[0].record_type().kind(), RecordKind::Treatment);
+    assert_eq!(diabetes.len(), 2);
+    assert_eq!(diabetes[1].gender(), Gender::Female);
+    assert_eq!(female.len(), 2);
+    assert_eq!(
+        female[1].record_type().kind(),
+        RecordKind::FamilyMedicalRecord
+    );
+
+    // The date range holds the treatment start and the medical record date it covers.
+    assert_eq!(spring.len(), 2);
+    assert_eq!(spring[0].record_type().kind(), RecordKind::Treatment);
+    assert_eq!(
+        spring[1].record_type().date(),
+        Some(Date::new(2023, 5, 6).unwrap())
+    );
+
+    // Pages are read within the index.
+    let second = contract.get_public_records_by_condition("Type 1 Diabetes".to_string(), 1, 1);
+    assert_eq!(second.len(), 1);
+    assert_eq!(second[0].gender(), Gender::Female);
+    assert!(contract
+        .get_public_records_by_condition("Cancer".to_string(), 0, 10)
+        .is_empty());
+}
+
+#[test]
+fn test_public_record_age_generalized() {
+    // Arrange
+    // Create a new context with the admin account, which initializes the contract.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "1990-06-15".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    // Act
+    // Publish a record with the default generalization, then with birth years only.
+    contract.add_public_medical_record(
+        "Flu".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(1), "first flu"),
+    );
+    testing_env!(context.predecessor_account_id(accounts(0)).build());
+    contract.set_age_generalization(AgeGeneralization::BirthYear);
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_public_medical_record(
+        "Flu".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(1), "second flu"),
+    );
+
+    // Assert
+    // The first record holds the 5-year band of the patient's age at the record's date.
+    let records = contract.get_public_records(0, 10);
+    assert_eq!(
+        records[0].age(),
+        GeneralizedAge::AgeBand { from: 30, to: 34 }
+    );
+    // The second record only holds the year of birth.
+    assert_eq!(records[1].age(), GeneralizedAge::BirthYear { year: 1990 });
+
+    // The exact birthday is not published.
+    let json = near_sdk::serde_json::to_string(&records).unwrap();
+    assert!(!json.contains("1990-06-15"));
+}
+
+#[test]
+fn test_anonymize_existing_public_records() {
+    // Arrange
+    // Create a new context with the admin account, which initializes the contract.
+    let context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    // Store a record published with the patient's exact birthday.
+    let record = PublicRecordV1::new(
+        RecordType::MedicalRecord(MedicalRecord::new(
+            0,
+            "Flu".to_string(),
+            "Record data".to_string(),
+            Date::new(2023, 1, 2).unwrap(),
+        )),
+        Date::new(1990, 6, 15).unwrap(),
+        Gender::Female,
+    );
+    contract
+        .public_records
+        .push(&VersionedPublicRecord::V1(record));
+
+    // The record is generalized when read, before being migrated.
+    assert_eq!(
+        contract.get_public_records(0, 10)[0].age(),
+        GeneralizedAge::AgeBand { from: 30, to: 34 }
+    );
+
+    // Act
+    // Rewrite the records published before ages were generalized.
+    let anonymized = contract.anonymize_public_records(0, 10);
+
+    // Assert
+    // The stored record no longer holds the birthday, and is rewritten only once.
+    assert_eq!(anonymized, 1);
+    assert!(contract.public_records.get(0).unwrap().is_generalized());
+    assert_eq!(contract.anonymize_public_records(0, 10), 0);
+    assert_eq!(
+        contract.get_public_records(0, 10)[0].age(),
+        GeneralizedAge::AgeBand { from: 30, to: 34 }
+    );
+}
+
+#[test]
+fn test_public_records_held_until_k_anonymous() {
+    // Arrange
+    // Create a new context with the owner account, which initializes the contract.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.set_k_anonymity(3);
+    // Register three patients in the same age band.
+    for (index, birthday) in ["1990-06-15", "1991-02-01", "1992-11-30"]
+        .iter()
+        .enumerate()
+    {
+        testing_env!(context.predecessor_account_id(accounts(index + 1)).build());
+        contract.add_patient(
+            "Jack Johnson".to_string(),
+            birthday.to_string(),
+            Gender::Male,
+            BloodType::APositive,
+        );
+    }
+
+    // Act
+    // Two patients publish a record about the same condition, and one about another condition.
+    for index in 1..=2 {
+        testing_env!(context.predecessor_account_id(accounts(index)).build());
+        contract.add_public_medical_record(
+            "Flu".to_string(),
+            "Record data".to_string(),
+            "2023-01-02".to_string(),
+            get_commitment(&accounts(index), "flu"),
+        );
+    }
+    contract.add_public_medical_record(
+        "Asthma".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(2), "asthma"),
+    );
+
+    // Assert
+    // The records are staged until k records share their age, gender and condition.
+    assert_eq!(contract.public_records_count(), 0);
+    assert_eq!(contract.staged_public_records_count(), 3);
+
+    // Act
+    // The third record about the same condition completes the group.
+    testing_env!(context.predecessor_account_id(accounts(3)).build());
+    contract.add_public_medical_record(
+        " FLU ".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(3), "flu"),
+    );
+
+    // Assert
+    assert_eq!(contract.public_records_count(), 3);
+    assert_eq!(contract.staged_public_records_count(), 1);
+    assert_eq!(
+        contract
+            .get_public_records_by_condition("flu".to_string(), 0, 10)
+            .len(),
+        3
+    );
+
+    // Act
+    // Later records joining the released group are released immediately.
+    contract.add_public_medical_record(
+        "Flu".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(3), "second flu"),
+    );
+
+    // Assert
+    assert_eq!(contract.public_records_count(), 4);
+}
+
+#[test]
+#[should_panic(expected = "k must be between 1 and 20.")]
+fn test_k_anonymity_capped() {
+    // Arrange
+    // Create a new context with the owner account, which initializes the contract.
+    let context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+
+    // Act & Assert
+    // A group of more than 20 records could not be released within a single call.
+    contract.set_k_anonymity(21);
+}
+
+#[test]
+fn test_release_staged_public_records_after_lowering_k() {
+    // Arrange
+    // Create a new context with the owner account, which initializes the contract.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.set_k_anonymity(3);
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "1990-06-15".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_public_medical_record(
+        "Flu".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(1), "first flu"),
+    );
+    contract.add_public_medical_record(
+        "Flu".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(1), "second flu"),
+    );
+    contract.add_public_medical_record(
+        "Asthma".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(1), "asthma"),
+    );
+
+    // Act
+    testing_env!(context.predecessor_account_id(accounts(0)).build());
+    contract.set_k_anonymity(2);
+    let released = contract.release_staged_public_records(0, 10);
+
+    // Assert
+    // Only the group that reached the new k is released.
+    assert_eq!(released, 2);
+    assert_eq!(contract.get_k_anonymity(), 2);
+    assert_eq!(contract.public_records_count(), 2);
+    assert_eq!(contract.staged_public_records_count(), 1);
+}
+
+#[test]
+fn test_withdraw_public_record() {
+    // Arrange
+    // Create a new context with the test account.
+    let context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "1990-06-15".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_public_medical_record(
+        "Flu".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(1), "first flu"),
+    );
+    contract.add_public_medical_record(
+        "Flu".to_string(),
+        "Record data".to_string(),
+        "2023-03-04".to_string(),
+        get_commitment(&accounts(1), "second flu"),
+    );
+
+    // Act
+    // Withdraw the first record by revealing the secret of its commitment.
+    testing_env!(context.build());
+    contract.withdraw_public_record(Base64VecU8(b"first flu".to_vec()));
+
+    // Assert
+    // The record is removed from the queries, but keeps its position.
+    let records = contract.get_public_records(0, 10);
+    assert_eq!(records.len(), 1);
+    assert_eq!(
+        records[0].contributor(),
+        Some(get_commitment(&accounts(1), "second flu"))
+    );
+    assert_eq!(contract.public_records_count(), 2);
+    assert_eq!(
+        contract
+            .get_public_records_by_condition("flu".to_string(), 0, 10)
+            .len(),
+        1
+    );
+    assert_eq!(
+        contract
+            .get_public_records_by_date("2023-01-01".to_string(), "2023-12-31".to_string(), 0, 10)
+            .len(),
+        1
+    );
+
+    // The withdrawal is tombstoned, without identifying the patient.
+    assert_eq!(contract.get_withdrawn_public_records(0, 10), vec![0]);
+    let events = get_events();
+    assert_eq!(events[0]["event"], "public_record_withdrawn");
+    assert_eq!(events[0]["data"]["index"], 0);
+    assert!(events[0]["data"].get("patient_id").is_none());
+}
+
+#[test]
+fn test_withdraw_staged_public_record() {
+    // Arrange
+    // Create a new context with the owner account, which initializes the contract.
+    let mut context = get_context(accounts(0));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.set_k_anonymity(3);
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "1990-06-15".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    for secret in ["first flu", "second flu"] {
+        contract.add_public_medical_record(
+            "Flu".to_string(),
+            "Record data".to_string(),
+            "2023-01-02".to_string(),
+            get_commitment(&accounts(1), secret),
+        );
+    }
+
+    // Act
+    // Withdraw the first record while it is staged.
+    contract.withdraw_public_record(Base64VecU8(b"first flu".to_vec()));
+
+    // Assert
+    // The record leaves the staging area and is never released.
+    assert_eq!(contract.staged_public_records_count(), 1);
+    assert!(contract.get_withdrawn_public_records(0, 10).is_empty());
+
+    // Two more records complete the group without the withdrawn one.
+    for secret in ["third flu", "fourth flu"] {
+        contract.add_public_medical_record(
+            "Flu".to_string(),
+            "Record data".to_string(),
+            "2023-01-02".to_string(),
+            get_commitment(&accounts(1), secret),
+        );
+    }
+    assert_eq!(contract.staged_public_records_count(), 0);
+    let contributors: Vec<_> = contract
+        .get_public_records(0, 10)
+        .iter()
+        .map(|record| record.contributor())
+        .collect();
+    assert_eq!(contributors.len(), 3);
+    assert!(!contributors.contains(&Some(get_commitment(&accounts(1), "first flu"))));
+
+    // The released records can still be withdrawn.
+    contract.withdraw_public_record(Base64VecU8(b"third flu".to_vec()));
+    assert_eq!(contract.get_public_records(0, 10).len(), 2);
+}
+
+#[test]
+fn test_public_records_v2_layout_read() {
+    // Arrange
+    // Create a new context with the test account.
+    let context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+
+    // Store a record in the layout published before contributor commitments were introduced.
+    let record_type = RecordType::MedicalRecord(MedicalRecord::new(
+        7,
+        "Flu".to_string(),
+        "Record data".to_string(),
+        Date::new(2023, 1, 2).unwrap(),
+    ));
+    let bytes = (
+        1u8,
+        record_type,
+        GeneralizedAge::AgeBand { from: 30, to: 34 },
+        Gender::Female,
+    )
+        .try_to_vec()
+        .unwrap();
+    contract
+        .public_records
+        .push(&VersionedPublicRecord::try_from_slice(&bytes).unwrap());
+
+    // Act
+    let records = contract.get_public_records(0, 10);
+
+    // Assert
+    // The record is read in the current layout, anonymized and without a contributor.
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].gender(), Gender::Female);
+    assert_eq!(records[0].record_type().id(), 0);
+    assert!(records[0].contributor().is_none());
+}
+
+#[test]
+#[should_panic(expected = "Public record not found.")]
+fn test_withdraw_public_record_of_another_patient_rejected() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "1990-06-15".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    contract.add_public_medical_record(
+        "Flu".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(1), "flu"),
+    );
+
+    // Act
+    // Another account revealing the same secret doesn't match the commitment.
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.withdraw_public_record(Base64VecU8(b"flu".to_vec()));
+}
+
+#[test]
+fn test_public_record_statistics() {
+    // Arrange
+    // Create a new context with the test account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "1990-06-15".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.add_patient(
+        "Jane Johnson".to_string(),
+        "1980-02-01".to_string(),
+        Gender::Female,
+        BloodType::ONegative,
+    );
+
+    // Act
+    // Publish records about the same condition for both patients, and a treatment.
+    contract.add_public_medical_record(
+        "Asthma".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(2), "asthma"),
+    );
+    testing_env!(context.predecessor_account_id(accounts(1)).build());
+    contract.add_public_medical_record(
+        "asthma".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(1), "first asthma"),
+    );
+    contract.add_family_medical_record(
+        "Asthma".to_string(),
+        "Father".to_string(),
+        true,
+        Some(get_commitment(&accounts(1), "second asthma")),
+    );
+    contract.add_treatment(
+        "Salbutamol".to_string(),
+        "2 puffs".to_string(),
+        "2023-01-02".to_string(),
+        "2023-02-02".to_string(),
+        None,
+        true,
+        Some(get_commitment(&accounts(1), "salbutamol")),
+    );
+    // Withdraw one of the records.
+    contract.withdraw_public_record(Base64VecU8(b"first asthma".to_vec()));
+
+    // Assert
+    // The counters only hold the records that weren't withdrawn, and
+    // family medical records are counted apart from the patients' conditions.
+    assert_eq!(contract.count_by_condition("ASTHMA".to_string()), 1);
+    assert_eq!(contract.count_by_family_condition("asthma".to_string()), 1);
+    assert_eq!(contract.count_by_treatment("salbutamol".to_string()), 1);
+    assert_eq!(contract.count_by_gender(Gender::Male), 2);
+    assert_eq!(contract.count_by_gender(Gender::Female), 1);
+    assert_eq!(contract.count_by_kind(RecordKind::MedicalRecord), 1);
+    assert_eq!(contract.count_by_condition("Flu".to_string()), 0);
+
+    // The distribution of the condition is cross-tabulated by gender and age,
+    // leaving out the relative's condition recorded under the patient's demographics.
+    let distribution = contract.get_condition_distribution("Asthma".to_string());
+    assert_eq!(distribution.len(), 1);
+    assert_eq!(distribution[0].gender(), Gender::Female);
+    assert_eq!(
+        distribution[0].age(),
+        GeneralizedAge::AgeBand { from: 40, to: 44 }
+    );
+    assert_eq!(distribution[0].count(), 1);
+}
+
+#[test]
+#[should_panic(expected = "Provider is not verified.")]
+fn test_unverified_emergency_provider_rejected() {
+    // Arrange
+    // Create a new context with the patient account.
+    let mut context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "2023-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    // Act & Assert
+    // A self-registered emergency provider can't use break-glass access until verified.
+    testing_env!(context.predecessor_account_id(accounts(2)).build());
+    contract.register_provider(
+        "Dr. Eve Adams".to_string(),
+        ProviderRole::Emergency,
+        "ER-001".to_string(),
+        "City ER".to_string(),
+    );
+    contract.emergency_access(accounts(1), "Unconscious on arrival".to_string());
+}
+
+#[test]
+fn test_public_medical_record_added_to_patient_records() {
+    // Arrange
+    // Create a new context with the test account.
+    let context = get_context(accounts(1));
+    // Initialize the mocked blockchain
+    testing_env!(context.build());
+
+    let mut contract = Contract::new();
+    contract.add_patient(
+        "Jack Johnson".to_string(),
+        "1990-01-02".to_string(),
+        Gender::Male,
+        BloodType::APositive,
+    );
+
+    // Act
+    // Publish a medical record.
+    let id = contract.add_public_medical_record(
+        "Type 1 Diabetes".to_string(),
+        "Record data".to_string(),
+        "2023-01-02".to_string(),
+        get_commitment(&accounts(1), "diabetes"),
+    );
+
+    // Assert
+    // The returned ID refers to the record in the patient's medical records.
+    let patient = contract.get_patient();
+    assert_eq!(patient.records().len(), 1);
+    assert_eq!(patient.records()[0].id(), id);
+    assert_eq!(
+        patient.records()[0].condition(),
+        Some("Type 1 Diabetes".to_string())
+    );
+
+    // The public copy carries no ID linking it to the patient's record.
+    let public_records = contract.get_all_public_records();
+    assert_eq!(public_records.len(), 1);
+    assert_eq!(public_records[0].record_type().id(), 0);
+}
@@ -1,7 +1,182 @@
+use crate::StorageKey;
 use near_sdk::{
     borsh::{self, BorshDeserialize, BorshSerialize},
-    serde::{Deserialize, Serialize},
+    collections::UnorderedMap,
+    env,
+    json_types::{Base64VecU8, U128},
+    serde::{de, Deserialize, Deserializer, Serialize, Serializer},
+    AccountId, Balance, PublicKey, StorageUsage,
 };
+use std::{fmt, mem, str::FromStr};
+
+/// Number of nanoseconds in a day, the unit of `env::block_timestamp()`
+const NANOS_PER_DAY: u64 = 86_400_000_000_000;
+
+/// Struct representing a validated calendar date.
+/// Dates are exchanged as ISO-8601 strings (`YYYY-MM-DD`) and ordered chronologically.
+#[derive(
+    BorshDeserialize, BorshSerialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug,
+)]
+pub struct Date {
+    year: u16,
+    month: u8,
+    day: u8,
+}
+
+impl Date {
+    /// Create a new date, checking that it exists in the calendar
+    pub fn new(year: u16, month: u8, day: u8) -> Result<Self, String> {
+        if !(1..=12).contains(&month) {
+            return Err(format!("Invalid month {} in date.", month));
+        }
+        if day == 0 || day > days_in_month(year, month) {
+            return Err(format!("Invalid day {} for {:04}-{:02}.", day, year, month));
+        }
+        Ok(Self { year, month, day })
+    }
+
+    /// Get the UTC calendar date of a block timestamp (in nanoseconds)
+    pub fn from_timestamp(timestamp: u64) -> Self {
+        // Civil-from-days conversion, counting days from 1970-01-01
+        let days = (timestamp / NANOS_PER_DAY) as i64 + 719_468;
+        let era = days / 146_097;
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let month_index = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+        let month = if month_index < 10 {
+            month_index + 3
+        } else {
+            month_index - 9
+        };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+        Self {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+        }
+    }
+
+    /// Convert a free-form date stored before dates were validated.
+    /// Accepts ISO-8601 dates as well as the `DD/MM/YYYY` and `DD-MM-YYYY`
+    /// formats used by earlier clients, and returns `None` for anything else.
+    pub fn from_legacy(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if let Ok(date) = value.parse() {
+            return Some(date);
+        }
+
+        let parts: Vec<&str> = value.split(['/', '-', '.']).collect();
+        match parts.as_slice() {
+            [day, month, year] if year.len() == 4 => {
+                Date::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?).ok()
+            }
+            [year, month, day] if year.len() == 4 => {
+                Date::new(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?).ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the year
+    pub fn year(&self) -> u16 {
+        self.year
+    }
+
+    /// Get the month (1-12)
+    pub fn month(&self) -> u8 {
+        self.month
+    }
+
+    /// Get the day of the month
+    pub fn day(&self) -> u8 {
+        self.day
+    }
+
+    /// Get the same calendar day the given number of years later.
+    /// February 29 becomes February 28 in non-leap years.
+    pub fn add_years(&self, years: u16) -> Self {
+        let year = self.year.saturating_add(years);
+        Self {
+            year,
+            month: self.month,
+            day: self.day.min(days_in_month(year, self.month)),
+        }
+    }
+
+    /// Get the number of full years elapsed between this date and the given later date
+    pub fn years_until(&self, other: &Date) -> u16 {
+        if other < self {
+            return 0;
+        }
+        let years = other.year - self.year;
+        if (other.month, other.day) < (self.month, self.day) {
+            years - 1
+        } else {
+            years
+        }
+    }
+}
+
+/// Return the number of days in the given month
+fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        // Leap years are divisible by 4, except centuries not divisible by 400
+        2 if matches!((year % 4, year % 100, year % 400), (0, 1.., _) | (_, _, 0)) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl FromStr for Date {
+    type Err = String;
+
+    /// Parse a strict ISO-8601 calendar date (`YYYY-MM-DD`)
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("'{}' is not an ISO-8601 date (YYYY-MM-DD).", value);
+        let bytes = value.as_bytes();
+        if bytes.len() != 10 || bytes[4] != b'-' || bytes[7] != b'-' {
+            return Err(invalid());
+        }
+        let number = |range: std::ops::Range<usize>| {
+            let part = &value[range];
+            if part.bytes().all(|b| b.is_ascii_digit()) {
+                part.parse().map_err(|_| invalid())
+            } else {
+                Err(invalid())
+            }
+        };
+        let year: u16 = number(0..4)?;
+        let month: u16 = number(5..7)?;
+        let day: u16 = number(8..10)?;
+
+        Date::new(year, month as u8, day as u8)
+            .map_err(|_| format!("'{}' is not a valid calendar date.", value))
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = <String as Deserialize>::deserialize(deserializer)?;
+        value.parse().map_err(de::Error::custom)
+    }
+}
 
 /// Struct representing a previous treatment received by the patient
 #[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
@@ -10,9 +185,13 @@ pub struct Treatment {
     id: u64,
     treatment: String,
     dosage: String,
-    start_date: String,
-    finish_date: String,
+    start_date: Date,
+    finish_date: Date,
     reason: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<AccountId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<Base64VecU8>,
 }
 
 impl Treatment {
@@ -21,8 +200,8 @@ impl Treatment {
         id: u64,
         treatment: String,
         dosage: String,
-        start_date: String,
-        finish_date: String,
+        start_date: Date,
+        finish_date: Date,
         reason: Option<String>,
     ) -> Self {
         Self {
@@ -32,8 +211,68 @@ impl Treatment {
             start_date,
             finish_date,
             reason,
+            author: None,
+            signature: None,
         }
     }
+
+    /// Get the ID assigned to the treatment
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Get the name of the treatment
+    pub fn treatment(&self) -> String {
+        self.treatment.clone()
+    }
+
+    /// Get the date the treatment started
+    pub fn start_date(&self) -> Date {
+        self.start_date
+    }
+
+    /// Get the date the treatment finishes
+    pub fn finish_date(&self) -> Date {
+        self.finish_date
+    }
+
+    /// Check whether the treatment is ongoing on the given date
+    pub fn is_active(&self, today: &Date) -> bool {
+        &self.start_date <= today && today <= &self.finish_date
+    }
+
+    /// Attribute the treatment to the provider who authored it
+    pub fn with_author(mut self, author: AccountId) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    /// Get the provider who authored the treatment, if not self-reported
+    pub fn author(&self) -> Option<AccountId> {
+        self.author.clone()
+    }
+
+    /// Attach the author's ed25519 signature over the treatment's signing payload
+    pub fn with_signature(mut self, signature: Vec<u8>) -> Self {
+        self.signature = Some(Base64VecU8(signature));
+        self
+    }
+
+    /// Get the author's ed25519 signature, if the treatment is signed
+    pub fn signature(&self) -> Option<Vec<u8>> {
+        self.signature.clone().map(Vec::from)
+    }
+
+    /// Get the bytes signed by the author for the given patient: the canonical Borsh
+    /// encoding of the patient's account ID, the nonce the author chose to make the
+    /// signature unique and the treatment, with its signature left out and its ID set
+    /// to zero, as the ID is only assigned by the contract once the signed treatment is stored
+    pub fn signing_payload(&self, patient_id: &AccountId, nonce: u64) -> Vec<u8> {
+        let mut unsigned = self.clone();
+        unsigned.id = 0;
+        unsigned.signature = None;
+        (patient_id, nonce, unsigned).try_to_vec().unwrap()
+    }
 }
 
 /// Struct representing a medical condition in the patient's family medical history
@@ -54,6 +293,16 @@ impl FamilyMedicalRecord {
             relation_to_patient,
         }
     }
+
+    /// Get the ID assigned to the family medical record
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Get the condition of the family member
+    pub fn condition(&self) -> String {
+        self.condition.clone()
+    }
 }
 
 /// Struct representing a personal medical record
@@ -63,19 +312,73 @@ pub struct MedicalRecord {
     id: u64,
     condition: String,
     record_data: String,
-    date: String,
+    date: Date,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<AccountId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    signature: Option<Base64VecU8>,
 }
 
 impl MedicalRecord {
     /// Create a new medical record with the given information
-    pub fn new(id: u64, condition: String, record_data: String, date: String) -> Self {
+    pub fn new(id: u64, condition: String, record_data: String, date: Date) -> Self {
         Self {
             id,
             condition,
             record_data,
             date,
+            author: None,
+            signature: None,
         }
     }
+
+    /// Get the ID assigned to the medical record
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Get the date of the medical record
+    pub fn date(&self) -> Date {
+        self.date
+    }
+
+    /// Get the condition the medical record is about
+    pub fn condition(&self) -> String {
+        self.condition.clone()
+    }
+
+    /// Attribute the medical record to the provider who authored it
+    pub fn with_author(mut self, author: AccountId) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    /// Get the provider who authored the medical record, if not self-reported
+    pub fn author(&self) -> Option<AccountId> {
+        self.author.clone()
+    }
+
+    /// Attach the author's ed25519 signature over the medical record's signing payload
+    pub fn with_signature(mut self, signature: Vec<u8>) -> Self {
+        self.signature = Some(Base64VecU8(signature));
+        self
+    }
+
+    /// Get the author's ed25519 signature, if the medical record is signed
+    pub fn signature(&self) -> Option<Vec<u8>> {
+        self.signature.clone().map(Vec::from)
+    }
+
+    /// Get the bytes signed by the author for the given patient: the canonical Borsh
+    /// encoding of the patient's account ID, the nonce the author chose to make the
+    /// signature unique and the medical record, with its signature left out and its ID set
+    /// to zero, as the ID is only assigned by the contract once the signed medical record is stored
+    pub fn signing_payload(&self, patient_id: &AccountId, nonce: u64) -> Vec<u8> {
+        let mut unsigned = self.clone();
+        unsigned.id = 0;
+        unsigned.signature = None;
+        (patient_id, nonce, unsigned).try_to_vec().unwrap()
+    }
 }
 
 // Enum representing the different types of records a patient can have
@@ -87,38 +390,459 @@ pub enum RecordType {
     FamilyMedicalRecord(FamilyMedicalRecord),
 }
 
-// Generic struct to hold any type of record and additional fields
+impl RecordType {
+    /// Return the access scope that covers this kind of record
+    pub fn scope(&self) -> AccessScope {
+        match self {
+            RecordType::MedicalRecord(_) => AccessScope::MedicalRecords,
+            RecordType::Treatment(_) => AccessScope::Treatments,
+            RecordType::FamilyMedicalRecord(_) => AccessScope::FamilyMedicalRecords,
+        }
+    }
+
+    /// Return the ID of the record
+    pub fn id(&self) -> u64 {
+        match self {
+            RecordType::MedicalRecord(record) => record.id(),
+            RecordType::Treatment(treatment) => treatment.id(),
+            RecordType::FamilyMedicalRecord(record) => record.id(),
+        }
+    }
+
+    /// Return the kind of the record
+    pub fn kind(&self) -> RecordKind {
+        match self {
+            RecordType::MedicalRecord(_) => RecordKind::MedicalRecord,
+            RecordType::Treatment(_) => RecordKind::Treatment,
+            RecordType::FamilyMedicalRecord(_) => RecordKind::FamilyMedicalRecord,
+        }
+    }
+
+    /// Return the condition the record is about. Treatments have no condition.
+    pub fn condition(&self) -> Option<String> {
+        match self {
+            RecordType::MedicalRecord(record) => Some(record.condition()),
+            RecordType::Treatment(_) => None,
+            RecordType::FamilyMedicalRecord(record) => Some(record.condition()),
+        }
+    }
+
+    /// Return the name of the treatment, for treatments
+    pub fn treatment(&self) -> Option<String> {
+        match self {
+            RecordType::Treatment(treatment) => Some(treatment.treatment()),
+            _ => None,
+        }
+    }
+
+    /// Return the date of the record: the date of a medical record or the start date
+    /// of a treatment. Family medical records have no date.
+    pub fn date(&self) -> Option<Date> {
+        match self {
+            RecordType::MedicalRecord(record) => Some(record.date()),
+            RecordType::Treatment(treatment) => Some(treatment.start_date()),
+            RecordType::FamilyMedicalRecord(_) => None,
+        }
+    }
+
+    /// Strip the record of what links it to the patient's copy: its ID is set to zero
+    /// and its author and signature are left out
+    pub fn anonymized(self) -> Self {
+        match self {
+            RecordType::MedicalRecord(record) => RecordType::MedicalRecord(MedicalRecord {
+                id: 0,
+                author: None,
+                signature: None,
+                ..record
+            }),
+            RecordType::Treatment(treatment) => RecordType::Treatment(Treatment {
+                id: 0,
+                author: None,
+                signature: None,
+                ..treatment
+            }),
+            RecordType::FamilyMedicalRecord(record) => {
+                RecordType::FamilyMedicalRecord(FamilyMedicalRecord { id: 0, ..record })
+            }
+        }
+    }
+}
+
+/// Enum representing the kinds of records, without their content
+#[derive(
+    BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum RecordKind {
+    MedicalRecord,
+    Treatment,
+    FamilyMedicalRecord,
+}
+
+// Generic struct to hold any type of record and additional fields.
+// The patient's age is generalized so that public records cannot be linked back to them.
 #[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct PublicRecord {
     record_type: RecordType,
-    birthday: String,
-    gender: String,
+    age: GeneralizedAge,
+    gender: Gender,
+    contributor: Option<Base64VecU8>,
 }
 
 impl PublicRecord {
-    /// Create a new public record with the given information
-    pub fn new(record_type: RecordType, birthday: String, gender: String) -> Self {
+    /// Create a new public record with the given information, anonymizing the record
+    pub fn new(
+        record_type: RecordType,
+        age: GeneralizedAge,
+        gender: Gender,
+        contributor: Option<Base64VecU8>,
+    ) -> Self {
+        Self {
+            record_type: record_type.anonymized(),
+            age,
+            gender,
+            contributor,
+        }
+    }
+
+    /// Get the anonymized record
+    pub fn record_type(&self) -> RecordType {
+        self.record_type.clone()
+    }
+
+    /// Get the generalized age of the patient the record belongs to
+    pub fn age(&self) -> GeneralizedAge {
+        self.age
+    }
+
+    /// Get the gender of the patient the record belongs to
+    pub fn gender(&self) -> Gender {
+        self.gender
+    }
+
+    /// Get the commitment the contributing patient proves ownership of to withdraw
+    /// the record: the sha256 hash of their account ID followed by a secret they keep.
+    /// Records published before commitments were introduced have none.
+    pub fn contributor(&self) -> Option<Base64VecU8> {
+        self.contributor.clone()
+    }
+}
+
+// Struct representing a public record as published before ages were generalized,
+// holding the patient's exact birthday
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PublicRecordV1 {
+    record_type: RecordType,
+    birthday: Date,
+    gender: Gender,
+}
+
+impl PublicRecordV1 {
+    /// Create a new public record with the patient's exact birthday
+    pub fn new(record_type: RecordType, birthday: Date, gender: Gender) -> Self {
         Self {
             record_type,
             birthday,
             gender,
         }
     }
+
+    /// Convert the record to the current layout, generalizing the patient's age
+    /// at the record's date, or at the given date for records without one
+    pub fn generalize(self, generalization: AgeGeneralization, today: Date) -> PublicRecord {
+        let on = self.record_type.date().unwrap_or(today);
+        PublicRecord::new(
+            self.record_type,
+            generalization.generalize(self.birthday, on),
+            self.gender,
+            None,
+        )
+    }
+}
+
+// Struct representing a public record as published before contributor commitments
+// were introduced
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PublicRecordV2 {
+    record_type: RecordType,
+    age: GeneralizedAge,
+    gender: Gender,
+}
+
+impl PublicRecordV2 {
+    /// Convert the record to the current layout, anonymizing it. It has no contributor commitment.
+    pub fn into_current(self) -> PublicRecord {
+        PublicRecord::new(self.record_type, self.age, self.gender, None)
+    }
+}
+
+// Enum representing the stored layouts of a public record.
+// A layout change adds a variant, which is converted to the current layout when read.
+// A withdrawn record is replaced by a tombstone, keeping the positions of the others.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum VersionedPublicRecord {
+    V1(PublicRecordV1),
+    V2(PublicRecordV2),
+    V3(PublicRecord),
+    Withdrawn { withdrawn_at: u64 },
+}
+
+impl VersionedPublicRecord {
+    /// Convert the stored public record to the current layout, generalizing
+    /// the exact birthday of records published before ages were generalized
+    /// and anonymizing records published before IDs were left out.
+    /// Returns None for withdrawn records.
+    pub fn into_current(
+        self,
+        generalization: AgeGeneralization,
+        today: Date,
+    ) -> Option<PublicRecord> {
+        match self {
+            VersionedPublicRecord::V1(record) => Some(record.generalize(generalization, today)),
+            VersionedPublicRecord::V2(record) => Some(record.into_current()),
+            VersionedPublicRecord::V3(record) => Some(record),
+            VersionedPublicRecord::Withdrawn { .. } => None,
+        }
+    }
+
+    /// Whether the record holds no exact birthday
+    pub fn is_generalized(&self) -> bool {
+        !matches!(self, VersionedPublicRecord::V1(_))
+    }
+}
+
+impl From<PublicRecord> for VersionedPublicRecord {
+    fn from(record: PublicRecord) -> Self {
+        VersionedPublicRecord::V3(record)
+    }
+}
+
+/// Enum representing how the age of patients is generalized in public records
+#[derive(
+    BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum AgeGeneralization {
+    /// Age at the record's date, in bands of the given number of years
+    AgeBand { years: u8 },
+    /// Year of birth only
+    BirthYear,
+}
+
+impl AgeGeneralization {
+    /// Generalize the age of a patient born on the given birthday, at the given date
+    pub fn generalize(&self, birthday: Date, on: Date) -> GeneralizedAge {
+        match self {
+            AgeGeneralization::AgeBand { years } => {
+                let years = u16::from(*years).max(1);
+                let from = birthday.years_until(&on) / years * years;
+                GeneralizedAge::AgeBand {
+                    from,
+                    to: from + years - 1,
+                }
+            }
+            AgeGeneralization::BirthYear => GeneralizedAge::BirthYear {
+                year: birthday.year(),
+            },
+        }
+    }
+}
+
+impl Default for AgeGeneralization {
+    fn default() -> Self {
+        AgeGeneralization::AgeBand { years: 5 }
+    }
+}
+
+/// Enum representing the generalized age of the patient a public record belongs to
+#[derive(
+    BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum GeneralizedAge {
+    /// Age at the record's date, between `from` and `to` years (inclusive)
+    AgeBand { from: u16, to: u16 },
+    /// Year of birth
+    BirthYear { year: u16 },
+}
+
+/// Enum representing an ABO/Rh blood type, exchanged as e.g. `"O+"` or `"AB-"`
+#[derive(
+    BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum BloodType {
+    #[serde(rename = "O+")]
+    OPositive,
+    #[serde(rename = "O-")]
+    ONegative,
+    #[serde(rename = "A+")]
+    APositive,
+    #[serde(rename = "A-")]
+    ANegative,
+    #[serde(rename = "B+")]
+    BPositive,
+    #[serde(rename = "B-")]
+    BNegative,
+    #[serde(rename = "AB+")]
+    ABPositive,
+    #[serde(rename = "AB-")]
+    ABNegative,
+}
+
+impl FromStr for BloodType {
+    type Err = String;
+
+    /// Parse a blood type leniently, accepting common spellings
+    /// such as `"0+"`, `"ab neg"`, `"A Rh+"` or `"B positive"`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let normalized: String = value
+            .to_uppercase()
+            .replace("RH", "")
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+        let (group, rh) = ["AB", "A", "B", "O", "0"]
+            .iter()
+            .find_map(|group| Some((*group, normalized.strip_prefix(group)?)))
+            .ok_or_else(|| format!("'{}' is not a known blood type.", value))?;
+        let positive = match rh {
+            "+" | "POS" | "POSITIVE" | "+VE" => true,
+            "-" | "NEG" | "NEGATIVE" | "-VE" => false,
+            _ => return Err(format!("'{}' is not a known blood type.", value)),
+        };
+
+        Ok(match (group, positive) {
+            ("O" | "0", true) => BloodType::OPositive,
+            ("O" | "0", false) => BloodType::ONegative,
+            ("A", true) => BloodType::APositive,
+            ("A", false) => BloodType::ANegative,
+            ("B", true) => BloodType::BPositive,
+            ("B", false) => BloodType::BNegative,
+            (_, true) => BloodType::ABPositive,
+            (_, false) => BloodType::ABNegative,
+        })
+    }
+}
+
+impl fmt::Display for BloodType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            BloodType::OPositive => "O+",
+            BloodType::ONegative => "O-",
+            BloodType::APositive => "A+",
+            BloodType::ANegative => "A-",
+            BloodType::BPositive => "B+",
+            BloodType::BNegative => "B-",
+            BloodType::ABPositive => "AB+",
+            BloodType::ABNegative => "AB-",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Enum representing the gender of a patient
+#[derive(
+    BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Gender {
+    Female,
+    Male,
+    Other,
+    /// The patient preferred not to disclose their gender
+    Undisclosed,
+}
+
+impl FromStr for Gender {
+    type Err = String;
+
+    /// Parse a gender leniently, accepting common spellings
+    /// such as `"F"`, `"woman"`, `"non-binary"` or `"prefer not to say"`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_lowercase().as_str() {
+            "f" | "female" | "woman" | "w" => Ok(Gender::Female),
+            "m" | "male" | "man" => Ok(Gender::Male),
+            "o" | "other" | "x" | "nb" | "non-binary" | "nonbinary" | "non binary" => {
+                Ok(Gender::Other)
+            }
+            "" | "u" | "unknown" | "undisclosed" | "n/a" | "prefer not to say" => {
+                Ok(Gender::Undisclosed)
+            }
+            _ => Err(format!("'{}' is not a known gender.", value)),
+        }
+    }
+}
+
+impl fmt::Display for Gender {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+/// Enum representing how severe a patient's reaction to an allergen is
+#[derive(
+    BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum AllergySeverity {
+    Mild,
+    Moderate,
+    Severe,
+    LifeThreatening,
+}
+
+impl FromStr for AllergySeverity {
+    type Err = String;
+
+    /// Parse an allergy severity leniently, accepting common spellings
+    /// such as `"low"`, `"medium"`, `"high"` or `"anaphylaxis"`
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value
+            .trim()
+            .to_lowercase()
+            .replace(['-', '_'], " ")
+            .as_str()
+        {
+            "mild" | "low" | "minor" => Ok(AllergySeverity::Mild),
+            "moderate" | "medium" | "mid" => Ok(AllergySeverity::Moderate),
+            "severe" | "high" | "serious" => Ok(AllergySeverity::Severe),
+            "life threatening" | "lifethreatening" | "critical" | "anaphylaxis"
+            | "anaphylactic" => Ok(AllergySeverity::LifeThreatening),
+            _ => Err(format!("'{}' is not a known allergy severity.", value)),
+        }
+    }
+}
+
+impl fmt::Display for AllergySeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
 }
 
 // Struct representing an Allergy
 #[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Allergy {
+    id: u64,
     allergen: String,
-    severity: String,
+    severity: AllergySeverity,
 }
 
 impl Allergy {
     /// Create a new allergy with the given information
-    pub fn new(allergen: String, severity: String) -> Self {
-        Self { allergen, severity }
+    pub fn new(id: u64, allergen: String, severity: AllergySeverity) -> Self {
+        Self {
+            id,
+            allergen,
+            severity,
+        }
+    }
+
+    /// Return the ID
+    pub fn id(&self) -> u64 {
+        self.id
     }
 
     /// Return the allergen
@@ -127,8 +851,8 @@ impl Allergy {
     }
 
     /// Return the severity
-    pub fn severity(&self) -> String {
-        self.severity.clone()
+    pub fn severity(&self) -> AllergySeverity {
+        self.severity
     }
 }
 
@@ -136,14 +860,20 @@ impl Allergy {
 #[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Immunization {
+    id: u64,
     name: String,
-    date: String,
+    date: Date,
 }
 
 impl Immunization {
     /// Create a new immunization with the given information
-    pub fn new(name: String, date: String) -> Self {
-        Self { name, date }
+    pub fn new(id: u64, name: String, date: Date) -> Self {
+        Self { id, name, date }
+    }
+
+    /// Return the ID
+    pub fn id(&self) -> u64 {
+        self.id
     }
 
     /// Return the name
@@ -152,8 +882,8 @@ impl Immunization {
     }
 
     /// Return the date
-    pub fn date(&self) -> String {
-        self.date.clone()
+    pub fn date(&self) -> Date {
+        self.date
     }
 }
 
@@ -161,29 +891,50 @@ impl Immunization {
 #[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
 #[serde(crate = "near_sdk::serde")]
 pub struct Procedure {
+    id: u64,
     name: String,
-    date: String,
+    date: Date,
     description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<AccountId>,
 }
 
 impl Procedure {
     /// Create a new procedure with the given information
-    pub fn new(name: String, date: String, description: String) -> Self {
+    pub fn new(id: u64, name: String, date: Date, description: String) -> Self {
         Self {
+            id,
             name,
             date,
             description,
+            author: None,
         }
     }
 
+    /// Attribute the procedure to the provider who authored it
+    pub fn with_author(mut self, author: AccountId) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    /// Get the provider who authored the procedure, if not self-reported
+    pub fn author(&self) -> Option<AccountId> {
+        self.author.clone()
+    }
+
+    /// Return the ID
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
     /// Return the name
     pub fn name(&self) -> String {
         self.name.clone()
     }
 
     /// Return the date
-    pub fn date(&self) -> String {
-        self.date.clone()
+    pub fn date(&self) -> Date {
+        self.date
     }
 
     /// Return the description
@@ -192,42 +943,23 @@ impl Procedure {
     }
 }
 
-// Struct representing a patient's medical information
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
-#[serde(crate = "near_sdk::serde")]
-pub struct Patient {
+// Struct representing a patient's demographics, stored apart from their records
+#[derive(BorshDeserialize, BorshSerialize, Clone)]
+pub struct Demographics {
     full_name: String,
-    birthday: String,
-    gender: String,
-    blood_type: String,
-    records: Vec<RecordType>,
-    allergies: Vec<Allergy>,
-    immunizations: Vec<Immunization>,
-    procedures: Vec<Procedure>,
+    birthday: Date,
+    gender: Gender,
+    blood_type: BloodType,
 }
 
-impl Patient {
-    /// Create a new patient with the given information
-    #[allow(clippy::too_many_arguments)]
-    pub fn new(
-        full_name: String,
-        birthday: String,
-        gender: String,
-        blood_type: String,
-        records: Vec<RecordType>,
-        allergies: Vec<Allergy>,
-        immunizations: Vec<Immunization>,
-        procedures: Vec<Procedure>,
-    ) -> Self {
+impl Demographics {
+    /// Create new demographics with the given information
+    pub fn new(full_name: String, birthday: Date, gender: Gender, blood_type: BloodType) -> Self {
         Self {
             full_name,
             birthday,
             gender,
             blood_type,
-            records,
-            allergies,
-            immunizations,
-            procedures,
         }
     }
 
@@ -237,49 +969,391 @@ impl Patient {
     }
 
     /// Update the birthday of the patient
-    pub fn update_birthday(&mut self, birthday: String) {
+    pub fn update_birthday(&mut self, birthday: Date) {
         self.birthday = birthday;
     }
 
-    /// Update the gender of the patient    
-    pub fn update_gender(&mut self, gender: String) {
+    /// Update the gender of the patient
+    pub fn update_gender(&mut self, gender: Gender) {
         self.gender = gender;
     }
 
     /// Update the blood type of the patient
-    pub fn update_blood_type(&mut self, blood_type: String) {
+    pub fn update_blood_type(&mut self, blood_type: BloodType) {
         self.blood_type = blood_type;
     }
 
-    /// Add a new allergy to the patient's allergies
-    pub fn add_allergy(&mut self, allergy: Allergy) {
-        self.allergies.push(allergy);
+    /// Get the patient's full name
+    pub fn full_name(&self) -> String {
+        self.full_name.clone()
     }
 
-    /// Add a new immunization to the patient's immunizations
-    pub fn add_immunization(&mut self, immunization: Immunization) {
-        self.immunizations.push(immunization);
+    /// Get the patient's birthday
+    pub fn birthday(&self) -> Date {
+        self.birthday
     }
 
-    /// Add a new procedure to the patient's procedures
-    pub fn add_procedure(&mut self, procedure: Procedure) {
-        self.procedures.push(procedure);
+    /// Get the patient's blood type
+    pub fn blood_type(&self) -> BloodType {
+        self.blood_type
     }
 
-    /// Add a new medical record of type MedicalRecord to the patient's records
-    pub fn add_medical_record(&mut self, medical_record: MedicalRecord) {
-        self.records.push(RecordType::MedicalRecord(medical_record));
+    /// Get the patient's gender
+    pub fn gender(&self) -> Gender {
+        self.gender
     }
+}
 
-    /// Add a new treatment record to the patient's records
-    pub fn add_treatment(&mut self, treatment_record: Treatment) {
-        self.records.push(RecordType::Treatment(treatment_record));
-    }
+// Enum representing the stored layouts of a patient's demographics.
+// A layout change adds a variant, which is converted to the current layout when read.
+#[derive(BorshDeserialize, BorshSerialize)]
+pub enum VersionedPatient {
+    V1(Demographics),
+}
 
-    /// Add a new family medical record to the patient's records
+impl VersionedPatient {
+    /// Convert the stored demographics to the current layout
+    pub fn into_current(self) -> Demographics {
+        match self {
+            VersionedPatient::V1(demographics) => demographics,
+        }
+    }
+}
+
+impl From<Demographics> for VersionedPatient {
+    fn from(demographics: Demographics) -> Self {
+        VersionedPatient::V1(demographics)
+    }
+}
+
+// Struct holding the sections of a patient's information, each in its own
+// prefixed collection keyed by record ID, so that adding, amending or
+// retracting an entry doesn't read or rewrite the rest of the patient's history
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct PatientRecords {
+    records: UnorderedMap<u64, RecordType>,
+    allergies: UnorderedMap<u64, Allergy>,
+    immunizations: UnorderedMap<u64, Immunization>,
+    procedures: UnorderedMap<u64, Procedure>,
+}
+
+impl PatientRecords {
+    /// Create the empty collections of the given patient
+    pub fn new(account_id: &AccountId) -> Self {
+        let account_hash = env::sha256(account_id.as_bytes());
+        Self {
+            records: UnorderedMap::new(
+                StorageKey::PatientRecords {
+                    account_hash: account_hash.clone(),
+                }
+                .try_to_vec()
+                .unwrap(),
+            ),
+            allergies: UnorderedMap::new(
+                StorageKey::PatientAllergies {
+                    account_hash: account_hash.clone(),
+                }
+                .try_to_vec()
+                .unwrap(),
+            ),
+            immunizations: UnorderedMap::new(
+                StorageKey::PatientImmunizations {
+                    account_hash: account_hash.clone(),
+                }
+                .try_to_vec()
+                .unwrap(),
+            ),
+            procedures: UnorderedMap::new(
+                StorageKey::PatientProcedures { account_hash }
+                    .try_to_vec()
+                    .unwrap(),
+            ),
+        }
+    }
+
+    /// Add a new allergy to the patient's allergies
+    pub fn add_allergy(&mut self, allergy: Allergy) {
+        self.allergies.insert(&allergy.id(), &allergy);
+    }
+
+    /// Add a new immunization to the patient's immunizations
+    pub fn add_immunization(&mut self, immunization: Immunization) {
+        self.immunizations.insert(&immunization.id(), &immunization);
+    }
+
+    /// Add a new procedure to the patient's procedures
+    pub fn add_procedure(&mut self, procedure: Procedure) {
+        self.procedures.insert(&procedure.id(), &procedure);
+    }
+
+    /// Add a new medical record of type MedicalRecord to the patient's records
+    pub fn add_medical_record(&mut self, medical_record: MedicalRecord) {
+        self.records.insert(
+            &medical_record.id(),
+            &RecordType::MedicalRecord(medical_record),
+        );
+    }
+
+    /// Add a new treatment record to the patient's records
+    pub fn add_treatment(&mut self, treatment_record: Treatment) {
+        self.records.insert(
+            &treatment_record.id(),
+            &RecordType::Treatment(treatment_record),
+        );
+    }
+
+    /// Add a new family medical record to the patient's records
     pub fn add_family_medical_record(&mut self, family_medical_record: FamilyMedicalRecord) {
+        self.records.insert(
+            &family_medical_record.id(),
+            &RecordType::FamilyMedicalRecord(family_medical_record),
+        );
+    }
+
+    /// Get every entry of the patient's records, allergies, immunizations and procedures
+    pub fn entries(&self) -> Vec<AmendableRecord> {
+        let records = self.records().into_iter().map(AmendableRecord::from);
+        let allergies = self.allergies().into_iter().map(AmendableRecord::Allergy);
+        let immunizations = self
+            .immunizations()
+            .into_iter()
+            .map(AmendableRecord::Immunization);
+        let procedures = self
+            .procedures()
+            .into_iter()
+            .map(AmendableRecord::Procedure);
+        records
+            .chain(allergies)
+            .chain(immunizations)
+            .chain(procedures)
+            .collect()
+    }
+
+    /// Find the entry with the given ID
+    pub fn entry(&self, id: u64) -> Option<AmendableRecord> {
         self.records
-            .push(RecordType::FamilyMedicalRecord(family_medical_record));
+            .get(&id)
+            .map(AmendableRecord::from)
+            .or_else(|| self.allergies.get(&id).map(AmendableRecord::Allergy))
+            .or_else(|| {
+                self.immunizations
+                    .get(&id)
+                    .map(AmendableRecord::Immunization)
+            })
+            .or_else(|| self.procedures.get(&id).map(AmendableRecord::Procedure))
+    }
+
+    /// Remove the entry with the given ID and return it
+    pub fn remove_entry(&mut self, id: u64) -> Option<AmendableRecord> {
+        let entry = self.entry(id)?;
+        match entry {
+            AmendableRecord::MedicalRecord(_)
+            | AmendableRecord::Treatment(_)
+            | AmendableRecord::FamilyMedicalRecord(_) => self.records.remove(&id).map(|_| ()),
+            AmendableRecord::Allergy(_) => self.allergies.remove(&id).map(|_| ()),
+            AmendableRecord::Immunization(_) => self.immunizations.remove(&id).map(|_| ()),
+            AmendableRecord::Procedure(_) => self.procedures.remove(&id).map(|_| ()),
+        };
+        Some(entry)
+    }
+
+    /// Replace the entry of the same kind and ID with the given one.
+    /// Returns `false` if the patient has no such entry.
+    pub fn replace_entry(&mut self, entry: AmendableRecord) -> bool {
+        let id = entry.id();
+        let same_kind = matches!(
+            self.entry(id),
+            Some(existing) if mem::discriminant(&existing) == mem::discriminant(&entry)
+        );
+        if same_kind {
+            self.add_entry(entry);
+        }
+        same_kind
+    }
+
+    /// Add an entry of any kind to the matching section
+    pub fn add_entry(&mut self, entry: AmendableRecord) {
+        match entry {
+            AmendableRecord::MedicalRecord(record) => self.add_medical_record(record),
+            AmendableRecord::Treatment(treatment) => self.add_treatment(treatment),
+            AmendableRecord::FamilyMedicalRecord(record) => self.add_family_medical_record(record),
+            AmendableRecord::Allergy(allergy) => self.add_allergy(allergy),
+            AmendableRecord::Immunization(immunization) => self.add_immunization(immunization),
+            AmendableRecord::Procedure(procedure) => self.add_procedure(procedure),
+        }
+    }
+
+    /// Remove every entry of the patient from the contract storage
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.allergies.clear();
+        self.immunizations.clear();
+        self.procedures.clear();
+    }
+
+    /// Get the patient's records, oldest first
+    pub fn records(&self) -> Vec<RecordType> {
+        sorted_by_id(self.records.values(), RecordType::id)
+    }
+
+    /// Get the patient's allergies, oldest first
+    pub fn allergies(&self) -> Vec<Allergy> {
+        sorted_by_id(self.allergies.values(), Allergy::id)
+    }
+
+    /// Get the patient's immunizations, oldest first
+    pub fn immunizations(&self) -> Vec<Immunization> {
+        sorted_by_id(self.immunizations.values(), Immunization::id)
+    }
+
+    /// Get the patient's procedures, oldest first
+    pub fn procedures(&self) -> Vec<Procedure> {
+        sorted_by_id(self.procedures.values(), Procedure::id)
+    }
+
+    /// Get all the information of the patient with the given demographics
+    pub fn patient(&self, demographics: &Demographics) -> Patient {
+        Patient::new(
+            demographics.full_name(),
+            demographics.birthday(),
+            demographics.gender(),
+            demographics.blood_type(),
+            self.records(),
+            self.allergies(),
+            self.immunizations(),
+            self.procedures(),
+        )
+    }
+
+    /// Get the critical information needed to treat the patient in an emergency
+    pub fn emergency_view(&self, demographics: &Demographics, today: &Date) -> EmergencyView {
+        EmergencyView {
+            blood_type: demographics.blood_type(),
+            allergies: self.allergies(),
+            treatments: self
+                .records()
+                .into_iter()
+                .filter_map(|record| match record {
+                    RecordType::Treatment(treatment) if treatment.is_active(today) => {
+                        Some(treatment)
+                    }
+                    _ => None,
+                })
+                .collect(),
+        }
+    }
+
+    /// Get a view of the patient containing only the sections covered by the given scopes.
+    /// Sections outside the scopes are not read from storage.
+    pub fn view(&self, demographics: &Demographics, scopes: &[AccessScope]) -> PatientView {
+        let demographics = scopes
+            .contains(&AccessScope::Demographics)
+            .then_some(demographics);
+        let record_scopes = [
+            AccessScope::MedicalRecords,
+            AccessScope::Treatments,
+            AccessScope::FamilyMedicalRecords,
+        ];
+
+        PatientView {
+            full_name: demographics.map(Demographics::full_name),
+            birthday: demographics.map(Demographics::birthday),
+            gender: demographics.map(Demographics::gender),
+            blood_type: demographics.map(Demographics::blood_type),
+            records: record_scopes
+                .iter()
+                .any(|scope| scopes.contains(scope))
+                .then(|| {
+                    self.records()
+                        .into_iter()
+                        .filter(|record| scopes.contains(&record.scope()))
+                        .collect()
+                }),
+            allergies: scopes
+                .contains(&AccessScope::Allergies)
+                .then(|| self.allergies()),
+            immunizations: scopes
+                .contains(&AccessScope::Immunizations)
+                .then(|| self.immunizations()),
+            procedures: scopes
+                .contains(&AccessScope::Procedures)
+                .then(|| self.procedures()),
+        }
+    }
+}
+
+/// Collect the values of a collection ordered by record ID, which follows insertion order
+fn sorted_by_id<T>(values: impl Iterator<Item = T>, id: impl Fn(&T) -> u64) -> Vec<T> {
+    let mut values: Vec<T> = values.collect();
+    values.sort_by_key(|value| id(value));
+    values
+}
+
+// Struct representing all of a patient's medical information, as returned by `get_patient`
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Patient {
+    full_name: String,
+    birthday: Date,
+    gender: Gender,
+    blood_type: BloodType,
+    records: Vec<RecordType>,
+    allergies: Vec<Allergy>,
+    immunizations: Vec<Immunization>,
+    procedures: Vec<Procedure>,
+}
+
+impl Patient {
+    /// Create a new patient with the given information
+    pub fn new(
+        full_name: String,
+        birthday: Date,
+        gender: Gender,
+        blood_type: BloodType,
+        records: Vec<RecordType>,
+        allergies: Vec<Allergy>,
+        immunizations: Vec<Immunization>,
+        procedures: Vec<Procedure>,
+    ) -> Self {
+        Self {
+            full_name,
+            birthday,
+            gender,
+            blood_type,
+            records,
+            allergies,
+            immunizations,
+            procedures,
+        }
+    }
+
+    /// Get the patient's demographics
+    pub fn demographics(&self) -> Demographics {
+        Demographics::new(
+            self.full_name(),
+            self.birthday(),
+            self.gender(),
+            self.blood_type(),
+        )
+    }
+
+    /// Get every entry of the patient's records, allergies, immunizations and procedures
+    pub fn entries(&self) -> Vec<AmendableRecord> {
+        let records = self.records().into_iter().map(AmendableRecord::from);
+        let allergies = self.allergies().into_iter().map(AmendableRecord::Allergy);
+        let immunizations = self
+            .immunizations()
+            .into_iter()
+            .map(AmendableRecord::Immunization);
+        let procedures = self
+            .procedures()
+            .into_iter()
+            .map(AmendableRecord::Procedure);
+        records
+            .chain(allergies)
+            .chain(immunizations)
+            .chain(procedures)
+            .collect()
     }
 
     /// Get the patient's full name
@@ -288,18 +1362,18 @@ impl Patient {
     }
 
     /// Get the patient's birthday
-    pub fn birthday(&self) -> String {
-        self.birthday.clone()
+    pub fn birthday(&self) -> Date {
+        self.birthday
     }
 
     /// Get the patient's blood type
-    pub fn blood_type(&self) -> String {
-        self.blood_type.clone()
+    pub fn blood_type(&self) -> BloodType {
+        self.blood_type
     }
 
     /// Get the patient's gender
-    pub fn gender(&self) -> String {
-        self.gender.clone()
+    pub fn gender(&self) -> Gender {
+        self.gender
     }
 
     /// Get the patient's records
@@ -322,3 +1396,864 @@ impl Patient {
         self.procedures.clone()
     }
 }
+
+// Struct representing the sections of a patient visible to another account.
+// Sections outside the granted scopes are left out.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PatientView {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    full_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    birthday: Option<Date>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gender: Option<Gender>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    blood_type: Option<BloodType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    records: Option<Vec<RecordType>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    allergies: Option<Vec<Allergy>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    immunizations: Option<Vec<Immunization>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    procedures: Option<Vec<Procedure>>,
+}
+
+impl PatientView {
+    /// Get the patient's full name, if visible
+    pub fn full_name(&self) -> Option<String> {
+        self.full_name.clone()
+    }
+
+    /// Get the patient's birthday, if visible
+    pub fn birthday(&self) -> Option<Date> {
+        self.birthday
+    }
+
+    /// Get the patient's gender, if visible
+    pub fn gender(&self) -> Option<Gender> {
+        self.gender
+    }
+
+    /// Get the patient's blood type, if visible
+    pub fn blood_type(&self) -> Option<BloodType> {
+        self.blood_type
+    }
+
+    /// Get the patient's visible records
+    pub fn records(&self) -> Option<Vec<RecordType>> {
+        self.records.clone()
+    }
+
+    /// Get the patient's allergies, if visible
+    pub fn allergies(&self) -> Option<Vec<Allergy>> {
+        self.allergies.clone()
+    }
+
+    /// Get the patient's immunizations, if visible
+    pub fn immunizations(&self) -> Option<Vec<Immunization>> {
+        self.immunizations.clone()
+    }
+
+    /// Get the patient's procedures, if visible
+    pub fn procedures(&self) -> Option<Vec<Procedure>> {
+        self.procedures.clone()
+    }
+}
+
+/// Enum representing the role of a registered healthcare provider
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum ProviderRole {
+    Doctor,
+    Nurse,
+    Pharmacist,
+    Lab,
+    Researcher,
+    /// Emergency department staff allowed to use break-glass access
+    Emergency,
+}
+
+// Struct representing a healthcare provider's profile
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Provider {
+    full_name: String,
+    role: ProviderRole,
+    license_number: String,
+    organization: String,
+    signing_key: Option<PublicKey>,
+}
+
+impl Provider {
+    /// Create a new provider with the given information
+    pub fn new(
+        full_name: String,
+        role: ProviderRole,
+        license_number: String,
+        organization: String,
+    ) -> Self {
+        Self {
+            full_name,
+            role,
+            license_number,
+            organization,
+            signing_key: None,
+        }
+    }
+
+    /// Get the provider's full name
+    pub fn full_name(&self) -> String {
+        self.full_name.clone()
+    }
+
+    /// Get the provider's role
+    pub fn role(&self) -> ProviderRole {
+        self.role
+    }
+
+    /// Get the provider's license number
+    pub fn license_number(&self) -> String {
+        self.license_number.clone()
+    }
+
+    /// Get the provider's organization
+    pub fn organization(&self) -> String {
+        self.organization.clone()
+    }
+
+    /// Get the ed25519 public key the provider signs records with, if registered
+    pub fn signing_key(&self) -> Option<PublicKey> {
+        self.signing_key.clone()
+    }
+
+    /// Update the ed25519 public key the provider signs records with
+    pub fn update_signing_key(&mut self, signing_key: PublicKey) {
+        self.signing_key = Some(signing_key);
+    }
+}
+
+// Struct representing the critical patient information disclosed through break-glass access
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EmergencyView {
+    blood_type: BloodType,
+    allergies: Vec<Allergy>,
+    treatments: Vec<Treatment>,
+}
+
+impl EmergencyView {
+    /// Get the patient's blood type
+    pub fn blood_type(&self) -> BloodType {
+        self.blood_type
+    }
+
+    /// Get the patient's allergies
+    pub fn allergies(&self) -> Vec<Allergy> {
+        self.allergies.clone()
+    }
+
+    /// Get the patient's active treatments
+    pub fn treatments(&self) -> Vec<Treatment> {
+        self.treatments.clone()
+    }
+}
+
+/// Enum representing a section of the patient's information that can be shared
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum AccessScope {
+    /// Full name, birthday, gender and blood type
+    Demographics,
+    MedicalRecords,
+    Treatments,
+    FamilyMedicalRecords,
+    Allergies,
+    Immunizations,
+    Procedures,
+}
+
+impl AccessScope {
+    /// Return every section of the patient's information
+    pub fn all() -> Vec<AccessScope> {
+        vec![
+            AccessScope::Demographics,
+            AccessScope::MedicalRecords,
+            AccessScope::Treatments,
+            AccessScope::FamilyMedicalRecords,
+            AccessScope::Allergies,
+            AccessScope::Immunizations,
+            AccessScope::Procedures,
+        ]
+    }
+}
+
+// Struct representing a patient's consent for another account to read their data
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AccessGrant {
+    grantee: AccountId,
+    scopes: Vec<AccessScope>,
+    granted_at: u64,
+    expires_at: Option<u64>,
+}
+
+impl AccessGrant {
+    /// Create a new access grant for the given account and sections.
+    /// A grant without `expires_at` stays valid until revoked.
+    pub fn new(
+        grantee: AccountId,
+        scopes: Vec<AccessScope>,
+        granted_at: u64,
+        expires_at: Option<u64>,
+    ) -> Self {
+        Self {
+            grantee,
+            scopes,
+            granted_at,
+            expires_at,
+        }
+    }
+
+    /// Get the account the access was granted to
+    pub fn grantee(&self) -> AccountId {
+        self.grantee.clone()
+    }
+
+    /// Get the sections of the patient's information covered by the grant
+    pub fn scopes(&self) -> Vec<AccessScope> {
+        self.scopes.clone()
+    }
+
+    /// Get the block timestamp (in nanoseconds) at which the access was granted
+    pub fn granted_at(&self) -> u64 {
+        self.granted_at
+    }
+
+    /// Get the block timestamp (in nanoseconds) at which the access expires, if any
+    pub fn expires_at(&self) -> Option<u64> {
+        self.expires_at
+    }
+
+    /// Check whether the grant has expired at the given block timestamp
+    pub fn is_expired(&self, now: u64) -> bool {
+        matches!(self.expires_at, Some(expires_at) if now >= expires_at)
+    }
+}
+
+/// Enum representing whether an access grant still authorizes reads
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum GrantStatus {
+    Active,
+    Expired,
+}
+
+// Struct representing an access grant as returned by the grant listing views
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct GrantView {
+    #[serde(flatten)]
+    grant: AccessGrant,
+    status: GrantStatus,
+}
+
+impl GrantView {
+    /// Create a new grant view for the given grant at the given block timestamp
+    pub fn new(grant: AccessGrant, now: u64) -> Self {
+        let status = if grant.is_expired(now) {
+            GrantStatus::Expired
+        } else {
+            GrantStatus::Active
+        };
+        Self { grant, status }
+    }
+
+    /// Get the underlying access grant
+    pub fn grant(&self) -> AccessGrant {
+        self.grant.clone()
+    }
+
+    /// Get the status of the grant
+    pub fn status(&self) -> GrantStatus {
+        self.status
+    }
+}
+
+// Struct representing a break-glass read of a patient's critical information
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EmergencyAccess {
+    provider: AccountId,
+    justification: String,
+    accessed_at: u64,
+}
+
+impl EmergencyAccess {
+    /// Create a new emergency access entry with the given information
+    pub fn new(provider: AccountId, justification: String, accessed_at: u64) -> Self {
+        Self {
+            provider,
+            justification,
+            accessed_at,
+        }
+    }
+
+    /// Get the provider who used break-glass access
+    pub fn provider(&self) -> AccountId {
+        self.provider.clone()
+    }
+
+    /// Get the justification given by the provider
+    pub fn justification(&self) -> String {
+        self.justification.clone()
+    }
+
+    /// Get the block timestamp (in nanoseconds) of the access
+    pub fn accessed_at(&self) -> u64 {
+        self.accessed_at
+    }
+}
+
+// Struct representing a read or write of a patient's information
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct AuditEntry {
+    actor: AccountId,
+    timestamp: u64,
+    method: String,
+    sections: Vec<AccessScope>,
+}
+
+impl AuditEntry {
+    /// Create a new audit entry with the given information
+    pub fn new(
+        actor: AccountId,
+        timestamp: u64,
+        method: String,
+        sections: Vec<AccessScope>,
+    ) -> Self {
+        Self {
+            actor,
+            timestamp,
+            method,
+            sections,
+        }
+    }
+
+    /// Get the account that read or wrote the information
+    pub fn actor(&self) -> AccountId {
+        self.actor.clone()
+    }
+
+    /// Get the block timestamp (in nanoseconds) of the access
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    /// Get the name of the contract method that was called
+    pub fn method(&self) -> String {
+        self.method.clone()
+    }
+
+    /// Get the sections of the patient's information that were accessed
+    pub fn sections(&self) -> Vec<AccessScope> {
+        self.sections.clone()
+    }
+}
+
+// Struct representing the accounts allowed to manage a dependant patient's information
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Guardianship {
+    guardians: Vec<AccountId>,
+    majority_at: Option<Date>,
+}
+
+impl Guardianship {
+    /// Create a new guardianship with the given information.
+    /// `majority_at` is only set for minors, who cannot manage their
+    /// guardians before that date.
+    pub fn new(guardians: Vec<AccountId>, majority_at: Option<Date>) -> Self {
+        Self {
+            guardians,
+            majority_at,
+        }
+    }
+
+    /// Get the guardians of the patient
+    pub fn guardians(&self) -> Vec<AccountId> {
+        self.guardians.clone()
+    }
+
+    /// Get the date at which a minor reaches majority
+    pub fn majority_at(&self) -> Option<Date> {
+        self.majority_at
+    }
+
+    /// Check whether the given account is a guardian of the patient
+    pub fn is_guardian(&self, account_id: &AccountId) -> bool {
+        self.guardians.contains(account_id)
+    }
+
+    /// Check whether the patient is still a minor on the given date
+    pub fn is_minor(&self, today: &Date) -> bool {
+        matches!(self.majority_at, Some(majority_at) if today < &majority_at)
+    }
+
+    /// Add a guardian to the patient
+    pub fn add_guardian(&mut self, guardian: AccountId) {
+        self.guardians.push(guardian);
+    }
+
+    /// Remove a guardian from the patient
+    pub fn remove_guardian(&mut self, guardian: &AccountId) {
+        self.guardians.retain(|account_id| account_id != guardian);
+    }
+}
+
+// Struct representing a guardian's request to register a minor as their dependant.
+// It only takes effect once the dependant's account accepts it.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingGuardianship {
+    guardian_id: AccountId,
+    full_name: String,
+    birthday: Date,
+    gender: Gender,
+    blood_type: BloodType,
+    deposit: U128,
+}
+
+impl PendingGuardianship {
+    /// Create a new pending guardianship with the given information
+    pub fn new(
+        guardian_id: AccountId,
+        full_name: String,
+        birthday: Date,
+        gender: Gender,
+        blood_type: BloodType,
+        deposit: Balance,
+    ) -> Self {
+        Self {
+            guardian_id,
+            full_name,
+            birthday,
+            gender,
+            blood_type,
+            deposit: U128(deposit),
+        }
+    }
+
+    /// Get the account asking to become the dependant's guardian
+    pub fn guardian_id(&self) -> AccountId {
+        self.guardian_id.clone()
+    }
+
+    /// Get the full name the dependant would be registered with
+    pub fn full_name(&self) -> String {
+        self.full_name.clone()
+    }
+
+    /// Get the birthday the dependant would be registered with
+    pub fn birthday(&self) -> Date {
+        self.birthday
+    }
+
+    /// Get the gender the dependant would be registered with
+    pub fn gender(&self) -> Gender {
+        self.gender
+    }
+
+    /// Get the blood type the dependant would be registered with
+    pub fn blood_type(&self) -> BloodType {
+        self.blood_type
+    }
+
+    /// Get the deposit attached by the guardian (in yoctoNEAR), added to the
+    /// dependant's storage balance on acceptance or refunded otherwise
+    pub fn deposit(&self) -> Balance {
+        self.deposit.0
+    }
+}
+
+// Enum representing the kinds of records a provider can submit for a patient
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub enum SubmittedRecord {
+    MedicalRecord(MedicalRecord),
+    Treatment(Treatment),
+    Procedure(Procedure),
+}
+
+impl SubmittedRecord {
+    /// Return the access scope that covers this kind of record
+    pub fn scope(&self) -> AccessScope {
+        match self {
+            SubmittedRecord::MedicalRecord(_) => AccessScope::MedicalRecords,
+            SubmittedRecord::Treatment(_) => AccessScope::Treatments,
+            SubmittedRecord::Procedure(_) => AccessScope::Procedures,
+        }
+    }
+
+    /// Get the ID assigned to the record on submission
+    pub fn id(&self) -> u64 {
+        match self {
+            SubmittedRecord::MedicalRecord(record) => record.id(),
+            SubmittedRecord::Treatment(treatment) => treatment.id(),
+            SubmittedRecord::Procedure(procedure) => procedure.id(),
+        }
+    }
+}
+
+// Struct representing a provider-authored record awaiting the patient's acknowledgement
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PendingRecord {
+    id: u64,
+    author: AccountId,
+    record: SubmittedRecord,
+    submitted_at: u64,
+}
+
+impl PendingRecord {
+    /// Create a new pending record with the given information
+    pub fn new(id: u64, author: AccountId, record: SubmittedRecord, submitted_at: u64) -> Self {
+        Self {
+            id,
+            author,
+            record,
+            submitted_at,
+        }
+    }
+
+    /// Get the ID of the pending record
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Get the provider who submitted the record
+    pub fn author(&self) -> AccountId {
+        self.author.clone()
+    }
+
+    /// Get the submitted record
+    pub fn record(&self) -> SubmittedRecord {
+        self.record.clone()
+    }
+
+    /// Get the block timestamp (in nanoseconds) at which the record was submitted
+    pub fn submitted_at(&self) -> u64 {
+        self.submitted_at
+    }
+}
+
+// Enum representing any entry of a patient's information that can be amended
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub enum AmendableRecord {
+    MedicalRecord(MedicalRecord),
+    Treatment(Treatment),
+    FamilyMedicalRecord(FamilyMedicalRecord),
+    Allergy(Allergy),
+    Immunization(Immunization),
+    Procedure(Procedure),
+}
+
+impl AmendableRecord {
+    /// Return the ID of the entry
+    pub fn id(&self) -> u64 {
+        match self {
+            AmendableRecord::MedicalRecord(record) => record.id(),
+            AmendableRecord::Treatment(treatment) => treatment.id(),
+            AmendableRecord::FamilyMedicalRecord(record) => record.id(),
+            AmendableRecord::Allergy(allergy) => allergy.id(),
+            AmendableRecord::Immunization(immunization) => immunization.id(),
+            AmendableRecord::Procedure(procedure) => procedure.id(),
+        }
+    }
+
+    /// Return the access scope that covers this kind of entry
+    pub fn scope(&self) -> AccessScope {
+        match self {
+            AmendableRecord::MedicalRecord(_) => AccessScope::MedicalRecords,
+            AmendableRecord::Treatment(_) => AccessScope::Treatments,
+            AmendableRecord::FamilyMedicalRecord(_) => AccessScope::FamilyMedicalRecords,
+            AmendableRecord::Allergy(_) => AccessScope::Allergies,
+            AmendableRecord::Immunization(_) => AccessScope::Immunizations,
+            AmendableRecord::Procedure(_) => AccessScope::Procedures,
+        }
+    }
+}
+
+impl From<RecordType> for AmendableRecord {
+    fn from(record: RecordType) -> Self {
+        match record {
+            RecordType::MedicalRecord(record) => AmendableRecord::MedicalRecord(record),
+            RecordType::Treatment(treatment) => AmendableRecord::Treatment(treatment),
+            RecordType::FamilyMedicalRecord(record) => AmendableRecord::FamilyMedicalRecord(record),
+        }
+    }
+}
+
+// Struct representing one version of an amended entry.
+// The original version has no amendment details.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct RecordVersion {
+    version: u32,
+    record: AmendableRecord,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    amended_at: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    amended_by: Option<AccountId>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+}
+
+impl RecordVersion {
+    /// Create the first version of an entry, as it was originally recorded
+    pub fn original(record: AmendableRecord) -> Self {
+        Self {
+            version: 1,
+            record,
+            amended_at: None,
+            amended_by: None,
+            reason: None,
+        }
+    }
+
+    /// Create a version amending the previous one
+    pub fn amendment(
+        version: u32,
+        record: AmendableRecord,
+        amended_at: u64,
+        amended_by: AccountId,
+        reason: String,
+    ) -> Self {
+        Self {
+            version,
+            record,
+            amended_at: Some(amended_at),
+            amended_by: Some(amended_by),
+            reason: Some(reason),
+        }
+    }
+
+    /// Get the version number, starting at 1 for the original
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Get the content of the entry in this version
+    pub fn record(&self) -> AmendableRecord {
+        self.record.clone()
+    }
+
+    /// Get the block timestamp (in nanoseconds) of the amendment, if not the original
+    pub fn amended_at(&self) -> Option<u64> {
+        self.amended_at
+    }
+
+    /// Get the account that made the amendment, if not the original
+    pub fn amended_by(&self) -> Option<AccountId> {
+        self.amended_by.clone()
+    }
+
+    /// Get the reason given for the amendment, if not the original
+    pub fn reason(&self) -> Option<String> {
+        self.reason.clone()
+    }
+}
+
+// Struct representing an entry retracted by the patient.
+// The entry is removed from the patient's information but kept for auditing.
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Tombstone {
+    record: AmendableRecord,
+    retracted_at: u64,
+    retracted_by: AccountId,
+    reason: String,
+}
+
+impl Tombstone {
+    /// Create a new tombstone with the given information
+    pub fn new(
+        record: AmendableRecord,
+        retracted_at: u64,
+        retracted_by: AccountId,
+        reason: String,
+    ) -> Self {
+        Self {
+            record,
+            retracted_at,
+            retracted_by,
+            reason,
+        }
+    }
+
+    /// Get the retracted entry, as it was when retracted
+    pub fn record(&self) -> AmendableRecord {
+        self.record.clone()
+    }
+
+    /// Get the block timestamp (in nanoseconds) at which the entry was retracted
+    pub fn retracted_at(&self) -> u64 {
+        self.retracted_at
+    }
+
+    /// Get the account that retracted the entry
+    pub fn retracted_by(&self) -> AccountId {
+        self.retracted_by.clone()
+    }
+
+    /// Get the reason given for the retraction
+    pub fn reason(&self) -> String {
+        self.reason.clone()
+    }
+}
+
+// Struct representing the NEAR an account deposited to pay for the storage of its information
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct StorageAccount {
+    total: Balance,
+    used_bytes: StorageUsage,
+}
+
+impl StorageAccount {
+    /// Create a new storage account with the given deposit and no storage used
+    pub fn new(total: Balance) -> Self {
+        Self {
+            total,
+            used_bytes: 0,
+        }
+    }
+
+    /// Get the total amount deposited (in yoctoNEAR)
+    pub fn total(&self) -> Balance {
+        self.total
+    }
+
+    /// Get the number of bytes of contract storage paid for by the account
+    pub fn used_bytes(&self) -> StorageUsage {
+        self.used_bytes
+    }
+
+    /// Get the amount locked to pay for the used storage (in yoctoNEAR)
+    pub fn locked(&self) -> Balance {
+        Balance::from(self.used_bytes) * env::storage_byte_cost()
+    }
+
+    /// Get the amount that is not locked by the used storage and can be withdrawn
+    pub fn available(&self) -> Balance {
+        self.total.saturating_sub(self.locked())
+    }
+
+    /// Add the given amount to the deposit
+    pub fn deposit(&mut self, amount: Balance) {
+        self.total += amount;
+    }
+
+    /// Remove the given amount from the deposit
+    pub fn withdraw(&mut self, amount: Balance) {
+        self.total -= amount;
+    }
+
+    /// Account for the given number of bytes of storage now used by the account
+    pub fn use_bytes(&mut self, bytes: StorageUsage) {
+        self.used_bytes += bytes;
+    }
+
+    /// Account for the given number of bytes of storage freed by the account
+    pub fn free_bytes(&mut self, bytes: StorageUsage) {
+        self.used_bytes = self.used_bytes.saturating_sub(bytes);
+    }
+}
+
+// Struct representing the storage balance of an account, as defined by NEP-145
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalance {
+    total: U128,
+    available: U128,
+}
+
+impl StorageBalance {
+    /// Get the total amount deposited (in yoctoNEAR)
+    pub fn total(&self) -> U128 {
+        self.total
+    }
+
+    /// Get the amount that can be withdrawn (in yoctoNEAR)
+    pub fn available(&self) -> U128 {
+        self.available
+    }
+}
+
+impl From<&StorageAccount> for StorageBalance {
+    fn from(account: &StorageAccount) -> Self {
+        Self {
+            total: U128(account.total()),
+            available: U128(account.available()),
+        }
+    }
+}
+
+// Struct representing the bounds of an account's storage balance, as defined by NEP-145
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct StorageBalanceBounds {
+    min: U128,
+    max: Option<U128>,
+}
+
+impl StorageBalanceBounds {
+    /// Create new storage balance bounds
+    pub fn new(min: U128, max: Option<U128>) -> Self {
+        Self { min, max }
+    }
+
+    /// Get the minimum balance required to register an account (in yoctoNEAR)
+    pub fn min(&self) -> U128 {
+        self.min
+    }
+
+    /// Get the maximum balance an account can need, if any (in yoctoNEAR)
+    pub fn max(&self) -> Option<U128> {
+        self.max
+    }
+}
+
+// Struct representing the number of public records about a condition
+// for one gender and generalized age
+#[derive(Deserialize, Serialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DemographicCount {
+    gender: Gender,
+    age: GeneralizedAge,
+    count: u64,
+}
+
+impl DemographicCount {
+    /// Create a new count with the given information
+    pub fn new(gender: Gender, age: GeneralizedAge, count: u64) -> Self {
+        Self { gender, age, count }
+    }
+
+    /// Get the gender of the patients counted
+    pub fn gender(&self) -> Gender {
+        self.gender
+    }
+
+    /// Get the generalized age of the patients counted
+    pub fn age(&self) -> GeneralizedAge {
+        self.age
+    }
+
+    /// Get the number of public records
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+}
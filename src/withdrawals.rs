@@ -0,0 +1,148 @@
+use super::*;
+
+/// Length (in bytes) of a contributor commitment, a sha256 hash
+const COMMITMENT_LENGTH: usize = 32;
+
+/// Where the public record committed to by a contributor commitment is kept
+#[derive(BorshDeserialize, BorshSerialize)]
+pub(crate) enum PublicRecordLocation {
+    /// Staged with the records sharing the given quasi-identifiers
+    Staged(QuasiIdentifier),
+    /// Released at the given position of the public records
+    Released(u64),
+}
+
+#[near_bindgen]
+impl Contract {
+    /// Withdraw the calling account's contribution to the public records, proving
+    /// ownership of the record's contributor commitment with the secret it was made from.
+    /// A released record is removed from future queries and replaced by a tombstone,
+    /// and a staged record is removed from the staging area before it is ever released.
+    pub fn withdraw_public_record(&mut self, secret: Base64VecU8) {
+        let account_id = env::predecessor_account_id();
+        self.internal_withdraw_public_record(account_id, secret, "withdraw_public_record");
+    }
+
+    /// Withdraw a contribution to the public records of a patient the calling account is guardian of
+    pub fn withdraw_public_record_for(&mut self, patient_id: AccountId, secret: Base64VecU8) {
+        self.assert_guardian(&patient_id);
+        self.internal_withdraw_public_record(patient_id, secret, "withdraw_public_record_for");
+    }
+
+    /// Get a page of the positions of the withdrawn public records, in the order
+    /// they were withdrawn, so that copies of the dataset can be purged
+    pub fn get_withdrawn_public_records(&self, from_index: u64, limit: u64) -> Vec<u64> {
+        let to_index = std::cmp::min(
+            from_index.saturating_add(std::cmp::min(limit, MAX_PUBLIC_RECORDS_PAGE)),
+            self.withdrawn_public_records.len(),
+        );
+        (from_index..to_index)
+            .filter_map(|index| self.withdrawn_public_records.get(index))
+            .collect()
+    }
+}
+
+impl Contract {
+    /// Check that a contributor commitment can be used for a record being published.
+    /// Each record needs its own commitment, so that withdrawing one
+    /// doesn't link the patient to their other records.
+    pub(crate) fn assert_unused_commitment(&self, commitment: &Base64VecU8) {
+        require!(
+            commitment.0.len() == COMMITMENT_LENGTH,
+            "Invalid contributor commitment."
+        );
+        require!(
+            !self.public_record_commitments.contains_key(&commitment.0),
+            "Contributor commitment already used."
+        );
+    }
+
+    /// Remove the public record committed to by the given patient and secret,
+    /// replacing it with a tombstone if it was released
+    fn internal_withdraw_public_record(
+        &mut self,
+        account_id: AccountId,
+        secret: Base64VecU8,
+        method: &str,
+    ) {
+        let initial_storage = env::storage_usage();
+        let commitment = env::sha256(&[account_id.as_bytes(), &secret.0].concat());
+        let location = self
+            .public_record_commitments
+            .get(&commitment)
+            .expect("Public record not found.");
+        self.public_record_commitments.remove(&commitment);
+
+        let record = match &location {
+            PublicRecordLocation::Staged(group) => {
+                self.internal_withdraw_staged_public_record(group, &commitment)
+            }
+            PublicRecordLocation::Released(index) => {
+                self.internal_withdraw_released_public_record(*index)
+            }
+        };
+
+        let scope = record.record_type().scope();
+        self.record_access(&account_id, method, vec![scope]);
+        self.charge_storage(&account_id, initial_storage);
+        match location {
+            PublicRecordLocation::Staged(_) => log!("Withdrew staged public record"),
+            PublicRecordLocation::Released(index) => {
+                Event::PublicRecordWithdrawn {
+                    index,
+                    section: scope,
+                }
+                .emit();
+                log!("Withdrew public record {}", index);
+            }
+        }
+    }
+
+    /// Remove the staged record carrying the given commitment from its group, and return it
+    fn internal_withdraw_staged_public_record(
+        &mut self,
+        group: &QuasiIdentifier,
+        commitment: &[u8],
+    ) -> PublicRecord {
+        let mut staged = self
+            .staged_public_records
+            .get(group)
+            .expect("Staged public records not found.");
+        let position = staged
+            .iter()
+            .position(|record| {
+                record
+                    .contributor()
+                    .map_or(false, |contributor| contributor.0 == commitment)
+            })
+            .expect("Public record not found.");
+        let record = staged.swap_remove(position as u64);
+
+        if staged.is_empty() {
+            self.staged_public_records.remove(group);
+        } else {
+            self.staged_public_records.insert(group, &staged);
+        }
+        self.total_staged_public_records -= 1;
+        record
+    }
+
+    /// Replace the released record at the given position with a tombstone, and return it
+    fn internal_withdraw_released_public_record(&mut self, index: u64) -> PublicRecord {
+        let record = self
+            .public_records
+            .get(index)
+            .and_then(|record| self.current_public_record(record))
+            .expect("Public record not found.");
+
+        self.unindex_public_record(index, &record);
+        self.public_records.replace(
+            index,
+            &VersionedPublicRecord::Withdrawn {
+                withdrawn_at: env::block_timestamp(),
+            },
+        );
+        self.withdrawn_public_records.push(&index);
+        record
+    }
+}